@@ -0,0 +1,351 @@
+use crate::chess::{Board, Move, PieceType, Player};
+use crate::engine::{Score, SearchInfo, StockfishEngine};
+use crate::pgn::{self, PgnMove};
+use crate::theme::ThemeArgs;
+use crate::tui::{self, BoardCursor, CommandLine, CursorAction, Layout, Line, MoveEntry, MoveList, Rect};
+use crate::ui::{BoardMarks, ChessUI};
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
+    execute, queue,
+    style::Color,
+    terminal::{self, ClearType},
+};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+const HELP: &str =
+    "Moves: e4, Nf3, e2e4 or click | play [N]: play line N | undo, redo | multipv N | fen [FEN] | reset | flip | eval | quit";
+
+#[derive(clap::Args)]
+pub struct AnalyzeArgs {
+    /// Position to analyse [default: the starting position]
+    #[arg(long)]
+    pub fen: Option<String>,
+
+    /// Number of lines to show
+    #[arg(long, default_value = "3")]
+    pub multipv: u32,
+
+    #[arg(short, long, default_value = "stockfish")]
+    pub engine_path: String,
+
+    #[command(flatten)]
+    pub theme: ThemeArgs,
+}
+
+pub fn run(args: AnalyzeArgs) -> Result<()> {
+    let appearance = args.theme.appearance()?;
+    let mut board = match args.fen {
+        Some(ref fen) => Board::from_fen(fen)?,
+        None => Board::new(),
+    };
+    let mut start = board.clone();
+    let mut multipv = args.multipv.max(1);
+    let mut engine = StockfishEngine::new(&args.engine_path)?;
+    engine.set_option("MultiPV", &multipv.to_string())?;
+
+    let mut ui = ChessUI::new(appearance);
+    let _terminal = tui::Terminal::enter()?;
+    let mut view = AnalysisView::new(&mut ui, &board);
+    let mut lines: Vec<Option<SearchInfo>> = vec![None; multipv as usize];
+
+    engine.start_analysis(&board)?;
+    view.draw(&ui, &board, &start, engine.name(), &lines)?;
+    let mut last_draw = Instant::now();
+    let mut dirty = false;
+
+    loop {
+        for info in engine.poll_analysis(Duration::from_millis(20))? {
+            if let Some(slot) = lines.get_mut(info.multipv.max(1) as usize - 1) {
+                *slot = Some(info);
+                dirty = true;
+            }
+        }
+        if dirty && last_draw.elapsed() >= REDRAW_INTERVAL {
+            view.draw(&ui, &board, &start, engine.name(), &lines)?;
+            last_draw = Instant::now();
+            dirty = false;
+        }
+        if !event::poll(Duration::from_millis(30))? {
+            continue;
+        }
+
+        let before = board.to_fen();
+        let mut restart = false;
+        match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    break;
+                }
+                let Some(command) = view.command.handle_key(key) else {
+                    view.draw(&ui, &board, &start, engine.name(), &lines)?;
+                    continue;
+                };
+                let command = command.trim();
+                let (word, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+                let argument = argument.trim();
+                view.message = (String::new(), None);
+
+                match word.to_lowercase().as_str() {
+                    "q" | "quit" | "exit" => break,
+                    "" => {}
+                    "u" | "undo" | "back" => {
+                        if board.undo_move().is_none() {
+                            view.error("Nothing to take back");
+                        }
+                    }
+                    "redo" => {
+                        if board.redo_move().is_none() {
+                            view.error("Nothing to redo");
+                        }
+                    }
+                    "play" => {
+                        let index = argument.parse::<usize>().unwrap_or(1).max(1) - 1;
+                        let best = lines
+                            .get(index)
+                            .and_then(|line| line.as_ref())
+                            .and_then(|info| pv_moves(&board, &info.pv).into_iter().next());
+                        match best {
+                            Some(chess_move) => board.make_move(&chess_move)?,
+                            None => view.error(&format!("No line {} yet", index + 1)),
+                        }
+                    }
+                    "multipv" => match argument.parse::<u32>() {
+                        Ok(count) if count >= 1 => {
+                            multipv = count;
+                            restart = true;
+                        }
+                        _ => view.error("Usage: multipv N"),
+                    },
+                    "fen" if argument.is_empty() => view.message = (format!("📋 {}", board.to_fen()), None),
+                    "fen" => match Board::from_fen(argument) {
+                        Ok(position) => {
+                            board = position;
+                            start = board.clone();
+                        }
+                        Err(e) => view.error(&e.to_string()),
+                    },
+                    "reset" => {
+                        board = Board::new();
+                        start = board.clone();
+                    }
+                    "flip" => {
+                        ui.flip();
+                        view.clear()?;
+                    }
+                    "eval" => {
+                        ui.toggle_eval_bar();
+                        view.fit(&mut ui)?;
+                        view.clear()?;
+                    }
+                    "help" | "h" => view.message = (HELP.to_string(), None),
+                    _ => match board.parse_move(command) {
+                        Ok(chess_move) => board.make_move(&chess_move)?,
+                        Err(e) => view.error(&e.to_string()),
+                    },
+                }
+            }
+            Event::Mouse(mouse) => {
+                if let Some(chess_move) = view.handle_mouse(mouse, &ui, &board) {
+                    board.make_move(&chess_move)?;
+                }
+            }
+            Event::Resize(..) => view.fit(&mut ui)?,
+            _ => continue,
+        }
+
+        if restart || board.to_fen() != before {
+            engine.stop_analysis()?;
+            if restart {
+                engine.set_option("MultiPV", &multipv.to_string())?;
+            }
+            lines = vec![None; multipv as usize];
+            view.cursor = BoardCursor::new(board.current_player());
+            engine.start_analysis(&board)?;
+        }
+        view.draw(&ui, &board, &start, engine.name(), &lines)?;
+        last_draw = Instant::now();
+    }
+
+    engine.stop_analysis()?;
+    Ok(())
+}
+
+/// The full-screen analysis: the board with the moves played below it, and the engine lines beside it.
+struct AnalysisView {
+    layout: Layout,
+    command: CommandLine,
+    message: Line,
+    /// Clicks on the board pick up and drop pieces.
+    cursor: BoardCursor,
+}
+
+impl AnalysisView {
+    fn new(ui: &mut ChessUI, board: &Board) -> Self {
+        let (columns, rows) = terminal::size().unwrap_or((80, 24));
+        ui.fit(columns, rows, tui::SPARE);
+        AnalysisView {
+            layout: Layout::new(columns, rows, ui.size()),
+            command: CommandLine::default(),
+            message: (String::new(), None),
+            cursor: BoardCursor::new(board.current_player()),
+        }
+    }
+
+    /// Picks the board size and layout for the terminal, clearing the screen when they change.
+    fn fit(&mut self, ui: &mut ChessUI) -> Result<()> {
+        let (columns, rows) = terminal::size().unwrap_or((80, 24));
+        ui.fit(columns, rows, tui::SPARE);
+        let layout = Layout::new(columns, rows, ui.size());
+        if layout.board != self.layout.board || layout.terminal != self.layout.terminal {
+            self.clear()?;
+        }
+        self.layout = layout;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        execute!(io::stdout(), terminal::Clear(ClearType::All))?;
+        Ok(())
+    }
+
+    fn error(&mut self, text: &str) {
+        self.message = (format!("❌ {}", text), Some(Color::Red));
+    }
+
+    /// A left click selects a piece or moves the selected one; promotions pick a queen.
+    fn handle_mouse(&mut self, mouse: MouseEvent, ui: &ChessUI, board: &Board) -> Option<Move> {
+        if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
+            return None;
+        }
+        let origin = (self.layout.board.x, self.layout.board.y);
+        let square = ui.square_at(origin, mouse.column, mouse.row)?;
+        let mut action = self.cursor.select(board, square);
+        if self.cursor.promotion.is_some() {
+            action = self.cursor.promote(board, PieceType::Queen);
+        }
+        match action {
+            CursorAction::Play(chess_move) => board.find_legal_move(chess_move.from(), chess_move.to(), chess_move.promotion()),
+            _ => None,
+        }
+    }
+
+    fn draw(&self, ui: &ChessUI, board: &Board, start: &Board, engine_name: &str, lines: &[Option<SearchInfo>]) -> Result<()> {
+        let layout = self.layout;
+        if !layout.fits() {
+            return tui::draw_too_small(&layout);
+        }
+        queue!(io::stdout(), cursor::Hide)?;
+        let marks = if self.cursor.selected.is_some() { self.cursor.marks(board) } else { BoardMarks::default() };
+        ui.draw_board(board, &marks, layout.board.x, layout.board.y)?;
+        let score = lines.first().and_then(|line| line.as_ref()).and_then(|info| info.score);
+        ui.draw_eval_bar(score, board.current_player(), layout.board.x, layout.board.y)?;
+
+        let depth = lines.iter().flatten().map(|info| info.depth).max();
+        let title = format!("🔍 {}{}", engine_name, depth.map_or(String::new(), |depth| format!(", depth {}", depth)));
+        tui::draw_lines(layout.clocks, &[(title, None), (board.to_fen(), Some(Color::DarkGrey))])?;
+        tui::draw_lines(layout.info, &ui.game_info(board, None))?;
+
+        let rect = layout.moves;
+        tui::draw_title(rect, "Lines")?;
+        tui::draw_lines(Rect::new(rect.x, rect.y + 1, rect.width, rect.height - 1), &line_rows(board, lines, rect.width as usize))?;
+
+        let rect = layout.engine;
+        let list = played_moves(start, board);
+        let current = list.entries.len().checked_sub(1);
+        let list_rect = Rect::new(rect.x, rect.y + 1, rect.width, rect.height.saturating_sub(1));
+        list.draw(list_rect, current, 0)?;
+        tui::draw_title(rect, "Moves")?;
+
+        let status = if self.message.0.is_empty() {
+            ("Type a move, play [N], undo, multipv N, fen, help or quit".to_string(), Some(Color::DarkGrey))
+        } else {
+            self.message.clone()
+        };
+        tui::draw_lines(layout.status, &[status])?;
+        self.command.draw(layout.command, "analyze> ")?;
+        io::stdout().flush()?;
+        Ok(())
+    }
+}
+
+/// Each engine line wrapped to `width`, continuation rows indented.
+fn line_rows(board: &Board, lines: &[Option<SearchInfo>], width: usize) -> Vec<Line> {
+    let mut rows = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let text = match line {
+            Some(info) => format!(
+                "{}. {:>6} d{:<3}{}",
+                index + 1,
+                info.score.map_or("?".to_string(), |score| format_score(score, board.current_player())),
+                info.depth,
+                pv_movetext(board, &info.pv)
+            ),
+            None => format!("{}. ...", index + 1),
+        };
+        let color = (index == 0).then_some(Color::Green);
+        for (row, part) in tui::wrap(&text, width.saturating_sub(3).max(1)).into_iter().enumerate() {
+            rows.push((if row == 0 { part } else { format!("   {}", part) }, color));
+        }
+    }
+    rows
+}
+
+/// The moves played since the analysis started or the position was set.
+fn played_moves(start: &Board, board: &Board) -> MoveList {
+    let mut position = start.clone();
+    let mut entries = Vec::new();
+    for chess_move in board.move_history().iter().skip(start.move_history().len()) {
+        entries.push(MoveEntry { san: position.to_san(chess_move), time: None, eval: None });
+        if position.make_move(chess_move).is_err() {
+            break;
+        }
+    }
+    MoveList { entries, first_number: start.move_count(), black_first: start.current_player() == Player::Black }
+}
+
+/// An engine score from White's point of view, e.g. `+0.35` or `#-3`.
+pub fn format_score(score: Score, side_to_move: Player) -> String {
+    let sign = if side_to_move == Player::White { 1 } else { -1 };
+    match score {
+        Score::Centipawns(cp) => format!("{:+.2}", (sign * cp) as f64 / 100.0),
+        Score::Mate(moves) => format!("#{}", sign * moves),
+    }
+}
+
+/// The legal prefix of a principal variation given in UCI notation.
+pub fn pv_moves(board: &Board, pv: &[String]) -> Vec<Move> {
+    let mut board = board.clone();
+    let mut moves = Vec::new();
+    for uci in pv {
+        let chess_move = match Move::from_uci(uci)
+            .ok()
+            .and_then(|m| board.find_legal_move(m.from(), m.to(), m.promotion()))
+        {
+            Some(chess_move) => chess_move,
+            None => break,
+        };
+        if board.make_move(&chess_move).is_err() {
+            break;
+        }
+        moves.push(chess_move);
+    }
+    moves
+}
+
+/// A principal variation as numbered SAN movetext, e.g. `12... Nf6 13. Bg5`.
+pub fn pv_movetext(board: &Board, pv: &[String]) -> String {
+    let mut position = board.clone();
+    let mut line = Vec::new();
+    for chess_move in pv_moves(board, pv) {
+        line.push(PgnMove { san: position.to_san(&chess_move), ..PgnMove::default() });
+        if position.make_move(&chess_move).is_err() {
+            break;
+        }
+    }
+    pgn::movetext(&line, board.move_count(), board.current_player() == Player::White)
+}
+
@@ -0,0 +1,255 @@
+use crate::analysis;
+use crate::chess::{Board, GameState, Player};
+use crate::engine::{Score, StockfishEngine};
+use crate::eval_graph;
+use crate::pgn::{self, PgnGame, PgnMove};
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Scores beyond this are treated as decided, so one blunder cannot swamp the averages.
+const SCORE_CAP: i32 = 1000;
+/// Best-line variations are cut to this many plies.
+const VARIATION_PLIES: usize = 8;
+
+#[derive(clap::Args)]
+pub struct AnnotateArgs {
+    /// PGN file with the games to annotate
+    pub file: PathBuf,
+
+    /// Where to write the annotated games [default: <file>-annotated.pgn]
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Only annotate this game (1-based)
+    #[arg(long)]
+    pub game: Option<usize>,
+
+    /// Engine thinking time per position (milliseconds)
+    #[arg(long, default_value = "500")]
+    pub movetime: u64,
+
+    #[arg(short, long, default_value = "stockfish")]
+    pub engine_path: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Judgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    /// Classifies a move by how much winning chance it threw away, in percentage points.
+    fn from_win_loss(loss: f64) -> Option<Self> {
+        if loss >= 15.0 {
+            Some(Judgement::Blunder)
+        } else if loss >= 10.0 {
+            Some(Judgement::Mistake)
+        } else if loss >= 5.0 {
+            Some(Judgement::Inaccuracy)
+        } else {
+            None
+        }
+    }
+
+    fn nag(self) -> u8 {
+        match self {
+            Judgement::Inaccuracy => 6,
+            Judgement::Mistake => 2,
+            Judgement::Blunder => 4,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "Inaccuracy",
+            Judgement::Mistake => "Mistake",
+            Judgement::Blunder => "Blunder",
+        }
+    }
+}
+
+/// Per-side statistics of an annotated game, indexed by `Player as usize`.
+#[derive(Debug, Default)]
+pub struct GameReport {
+    moves: [u32; 2],
+    centipawn_loss: [f64; 2],
+    accuracy: [f64; 2],
+    judgements: [[u32; 3]; 2],
+}
+
+impl GameReport {
+    pub fn print(&self) {
+        for player in [Player::White, Player::Black] {
+            let side = player as usize;
+            let moves = self.moves[side].max(1) as f64;
+            println!(
+                "  {:?}: {} inaccuracies, {} mistakes, {} blunders | ACPL {:.0} | Accuracy {:.1}%",
+                player,
+                self.judgements[side][0],
+                self.judgements[side][1],
+                self.judgements[side][2],
+                self.centipawn_loss[side] / moves,
+                self.accuracy[side] / moves
+            );
+        }
+    }
+}
+
+pub fn run(args: AnnotateArgs) -> Result<()> {
+    let text = fs::read_to_string(&args.file)
+        .map_err(|e| anyhow!("Failed to read '{}': {}", args.file.display(), e))?;
+    let mut games = pgn::parse_games(&text)?;
+    if let Some(number) = args.game {
+        if number == 0 || number > games.len() {
+            return Err(anyhow!("No game {} (the file has {})", number, games.len()));
+        }
+        games = vec![games.swap_remove(number - 1)];
+    }
+    if games.is_empty() {
+        return Err(anyhow!("No games found in '{}'", args.file.display()));
+    }
+
+    let output = args.output.clone().unwrap_or_else(|| {
+        let stem = args.file.file_stem().map_or("games".into(), |stem| stem.to_string_lossy());
+        args.file.with_file_name(format!("{}-annotated.pgn", stem))
+    });
+
+    let mut engine = StockfishEngine::new(&args.engine_path)?;
+    let time_limit = Duration::from_millis(args.movetime);
+    let mut annotated = String::new();
+    let count = games.len();
+
+    for (index, game) in games.iter_mut().enumerate() {
+        println!(
+            "🔍 Annotating game {}/{}: {} - {} ({} plies)",
+            index + 1,
+            count,
+            game.tag("White").unwrap_or("?"),
+            game.tag("Black").unwrap_or("?"),
+            game.moves.len()
+        );
+        let report = annotate_game(&mut engine, game, time_limit)?;
+        report.print();
+        if let Some(evals) = eval_graph::game_evals(game) {
+            eval_graph::draw(game, &evals, None)?;
+        }
+        println!();
+        annotated.push_str(&game.to_pgn());
+    }
+
+    fs::write(&output, annotated)?;
+    println!("💾 Annotated games written to {}", output.display());
+    Ok(())
+}
+
+/// Evaluates every mainline position, then adds `[%eval]` comments, NAGs and best-line variations.
+pub fn annotate_game(engine: &mut StockfishEngine, game: &mut PgnGame, time_limit: Duration) -> Result<GameReport> {
+    let boards = game.mainline_boards()?;
+    engine.new_game()?;
+
+    // Scores are from the point of view of the side to move in each position.
+    let mut evaluations = Vec::with_capacity(boards.len());
+    for board in &boards {
+        evaluations.push(evaluate(engine, board, time_limit)?);
+    }
+
+    let mut report = GameReport::default();
+    for (ply, pgn_move) in game.moves.iter_mut().enumerate() {
+        let before = &boards[ply];
+        let mover = before.current_player();
+        let (best_score, ref best_line) = evaluations[ply];
+        let (reply_score, _) = evaluations[ply + 1];
+
+        let best = capped(best_score);
+        let played = -capped(reply_score);
+        let side = mover as usize;
+        let win_loss = (win_percent(best) - win_percent(played)).max(0.0);
+        report.moves[side] += 1;
+        report.centipawn_loss[side] += (best - played).max(0) as f64;
+        report.accuracy[side] += move_accuracy(win_loss);
+
+        // A mated position has no evaluation to show.
+        let mut comment = match (reply_score, boards[ply + 1].current_player()) {
+            (Score::Mate(0), _) => String::new(),
+            (score, Player::White) => format!("[%eval {}]", format_eval(score)),
+            (score, Player::Black) => format!("[%eval {}]", format_eval(score.negate())),
+        };
+
+        let best_move = analysis::pv_moves(before, best_line).into_iter().next();
+        let played_best = best_move.is_some() && before.parse_san(&pgn_move.san).ok() == best_move;
+        if let Some(judgement) = Judgement::from_win_loss(win_loss).filter(|_| !played_best) {
+            report.judgements[side][judgement as usize] += 1;
+            pgn_move.nags.retain(|nag| !(1..=6).contains(nag));
+            pgn_move.nags.push(judgement.nag());
+
+            let variation = best_variation(before, best_line);
+            if let Some(first) = variation.first() {
+                comment = format!("{} {}. {} was best.", comment, judgement.name(), first.san);
+            }
+            if !variation.is_empty() {
+                pgn_move.variations.insert(0, variation);
+            }
+        }
+
+        let comment = comment.trim();
+        pgn_move.comment = match pgn_move.comment.take() {
+            Some(existing) if !comment.is_empty() => Some(format!("{} {}", comment, existing)),
+            Some(existing) => Some(existing),
+            None if !comment.is_empty() => Some(comment.to_string()),
+            None => None,
+        };
+    }
+
+    Ok(report)
+}
+
+fn evaluate(engine: &mut StockfishEngine, board: &Board, time_limit: Duration) -> Result<(Score, Vec<String>)> {
+    match board.game_state() {
+        GameState::Checkmate => return Ok((Score::Mate(0), Vec::new())),
+        GameState::Stalemate | GameState::Draw => return Ok((Score::Centipawns(0), Vec::new())),
+        GameState::InProgress => {}
+    }
+    let result = engine.analyse(board, time_limit)?;
+    let score = result
+        .info
+        .score
+        .ok_or_else(|| anyhow!("Engine '{}' gave no score for {}", engine.name(), board.to_fen()))?;
+    Ok((score, result.info.pv))
+}
+
+fn best_variation(board: &Board, pv: &[String]) -> Vec<PgnMove> {
+    let mut position = board.clone();
+    let mut variation = Vec::new();
+    for chess_move in analysis::pv_moves(board, pv).into_iter().take(VARIATION_PLIES) {
+        variation.push(PgnMove { san: position.to_san(&chess_move), ..PgnMove::default() });
+        if position.make_move(&chess_move).is_err() {
+            break;
+        }
+    }
+    variation
+}
+
+fn capped(score: Score) -> i32 {
+    score.as_centipawns().clamp(-SCORE_CAP, SCORE_CAP)
+}
+
+/// Expected score in percent for a centipawn advantage (the curve Lichess fits to its games).
+pub fn win_percent(centipawns: i32) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.003_682_08 * centipawns as f64).exp()) - 1.0)
+}
+
+fn move_accuracy(win_loss: f64) -> f64 {
+    (103.166_8 * (-0.043_54 * win_loss).exp() - 3.166_9).clamp(0.0, 100.0)
+}
+
+/// A White-relative score in the `[%eval]` format: pawns, or `#N` for mates.
+fn format_eval(score: Score) -> String {
+    match score {
+        Score::Centipawns(cp) => format!("{:.2}", cp as f64 / 100.0),
+        Score::Mate(moves) => format!("#{}", moves),
+    }
+}
@@ -2,6 +2,20 @@ use super::{Move, Piece, PieceType, Player, Position};
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
 
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2),
+];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1),
+];
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const PROMOTION_TYPES: [PieceType; 4] = [
+    PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight,
+];
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum GameState {
     InProgress,
@@ -10,6 +24,30 @@ pub enum GameState {
     Draw,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl GameResult {
+    pub fn win_for(player: Player) -> Self {
+        match player {
+            Player::White => GameResult::WhiteWins,
+            Player::Black => GameResult::BlackWins,
+        }
+    }
+
+    pub fn to_pgn(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Board {
     pieces: HashMap<Position, Piece>,
@@ -18,6 +56,20 @@ pub struct Board {
     halfmove_clock: u32,
     castling_rights: CastlingRights,
     en_passant_target: Option<Position>,
+    position_history: Vec<String>,
+    history: Vec<MoveRecord>,
+    undone: Vec<Move>,
+}
+
+/// A played move with the state needed to take it back.
+#[derive(Debug, Clone)]
+struct MoveRecord {
+    chess_move: Move,
+    piece: Piece,
+    captured: Option<(Position, Piece)>,
+    castling_rights: CastlingRights,
+    en_passant_target: Option<Position>,
+    halfmove_clock: u32,
 }
 
 #[derive(Debug, Clone)]
@@ -28,6 +80,45 @@ struct CastlingRights {
     black_queenside: bool,
 }
 
+impl CastlingRights {
+    fn none() -> Self {
+        CastlingRights {
+            white_kingside: false,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: false,
+        }
+    }
+
+    fn has(&self, player: Player, kingside: bool) -> bool {
+        match (player, kingside) {
+            (Player::White, true) => self.white_kingside,
+            (Player::White, false) => self.white_queenside,
+            (Player::Black, true) => self.black_kingside,
+            (Player::Black, false) => self.black_queenside,
+        }
+    }
+
+    // Any move touching a king or rook home square loses the matching right.
+    fn touch(&mut self, square: Position) {
+        match (square.file, square.rank) {
+            (4, 0) => {
+                self.white_kingside = false;
+                self.white_queenside = false;
+            }
+            (4, 7) => {
+                self.black_kingside = false;
+                self.black_queenside = false;
+            }
+            (7, 0) => self.white_kingside = false,
+            (0, 0) => self.white_queenside = false,
+            (7, 7) => self.black_kingside = false,
+            (0, 7) => self.black_queenside = false,
+            _ => {}
+        }
+    }
+}
+
 impl Board {
     pub fn new() -> Self {
         let mut board = Board {
@@ -42,11 +133,92 @@ impl Board {
                 black_queenside: true,
             },
             en_passant_target: None,
+            position_history: Vec::new(),
+            history: Vec::new(),
+            undone: Vec::new(),
         };
         
         board.setup_initial_position();
+        board.position_history.push(board.repetition_key());
         board
     }
+
+    pub fn from_fen(fen: &str) -> Result<Self> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 {
+            return Err(anyhow!("Invalid FEN: {}", fen));
+        }
+
+        let mut pieces = HashMap::new();
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(anyhow!("Invalid FEN piece placement: {}", fields[0]));
+        }
+        for (index, rank_str) in ranks.iter().enumerate() {
+            let rank = 7 - index as u8;
+            let mut file = 0u8;
+            for c in rank_str.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    file += skip as u8;
+                } else {
+                    let piece = Piece::from_fen_char(c)
+                        .ok_or_else(|| anyhow!("Invalid FEN piece: {}", c))?;
+                    pieces.insert(Position::new(file, rank)?, piece);
+                    file += 1;
+                }
+            }
+            if file != 8 {
+                return Err(anyhow!("Invalid FEN rank: {}", rank_str));
+            }
+        }
+
+        let current_player = match fields[1] {
+            "w" => Player::White,
+            "b" => Player::Black,
+            other => return Err(anyhow!("Invalid FEN side to move: {}", other)),
+        };
+
+        let mut castling_rights = CastlingRights::none();
+        for c in fields[2].chars() {
+            match c {
+                'K' => castling_rights.white_kingside = true,
+                'Q' => castling_rights.white_queenside = true,
+                'k' => castling_rights.black_kingside = true,
+                'q' => castling_rights.black_queenside = true,
+                '-' => {}
+                _ => return Err(anyhow!("Invalid FEN castling rights: {}", fields[2])),
+            }
+        }
+
+        let en_passant_target = match fields[3] {
+            "-" => None,
+            square => Some(Position::from_algebraic(square)?),
+        };
+
+        let halfmove_clock = fields.get(4).map_or(Ok(0), |s| s.parse())?;
+        let move_count = fields.get(5).map_or(Ok(1), |s| s.parse())?;
+
+        let mut board = Board {
+            pieces,
+            current_player,
+            move_count,
+            halfmove_clock,
+            castling_rights,
+            en_passant_target,
+            position_history: Vec::new(),
+            history: Vec::new(),
+            undone: Vec::new(),
+        };
+
+        for player in [Player::White, Player::Black] {
+            if board.king_position(player).is_none() {
+                return Err(anyhow!("Invalid FEN: missing {:?} king", player));
+            }
+        }
+
+        board.position_history.push(board.repetition_key());
+        Ok(board)
+    }
     
     fn setup_initial_position(&mut self) {
         self.pieces.insert(Position::new(0, 0).unwrap(), Piece::new(PieceType::Rook, Player::White));
@@ -88,19 +260,56 @@ impl Board {
         self.move_count
     }
     
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    pub fn king_position(&self, player: Player) -> Option<Position> {
+        Self::find_king(&self.pieces, player)
+    }
+
     pub fn make_move(&mut self, chess_move: &Move) -> Result<()> {
         if !self.is_legal_move(chess_move) {
             return Err(anyhow!("Illegal move"));
         }
         
-        if let Some(piece) = self.pieces.remove(&chess_move.from()) {
-            self.pieces.insert(chess_move.to(), piece);
+        let piece = match self.pieces.get(&chess_move.from()) {
+            Some(piece) => *piece,
+            None => return Err(anyhow!("No piece on {}", chess_move.from())),
+        };
+        let captured = if self.is_en_passant_capture(piece, chess_move) {
+            let square = Position { file: chess_move.to().file, rank: chess_move.from().rank };
+            self.pieces.get(&square).map(|captured| (square, *captured))
+        } else {
+            self.pieces.get(&chess_move.to()).map(|captured| (chess_move.to(), *captured))
+        };
+        let is_capture = captured.is_some();
+        self.history.push(MoveRecord {
+            chess_move: chess_move.clone(),
+            piece,
+            captured,
+            castling_rights: self.castling_rights.clone(),
+            en_passant_target: self.en_passant_target,
+            halfmove_clock: self.halfmove_clock,
+        });
+
+        Self::move_pieces(&mut self.pieces, chess_move, self.en_passant_target);
+
+        self.en_passant_target = None;
+        if piece.piece_type == PieceType::Pawn
+            && (chess_move.to().rank as i8 - chess_move.from().rank as i8).abs() == 2
+        {
+            let middle_rank = (chess_move.from().rank + chess_move.to().rank) / 2;
+            self.en_passant_target = Some(Position::new(chess_move.from().file, middle_rank)?);
         }
         
-        if let Some(promotion_type) = chess_move.promotion() {
-            if let Some(piece) = self.pieces.get_mut(&chess_move.to()) {
-                piece.piece_type = promotion_type;
-            }
+        self.castling_rights.touch(chess_move.from());
+        self.castling_rights.touch(chess_move.to());
+
+        if piece.piece_type == PieceType::Pawn || is_capture {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
         }
         
         self.current_player = self.current_player.opposite();
@@ -108,37 +317,422 @@ impl Board {
             self.move_count += 1;
         }
         
+        self.position_history.push(self.repetition_key());
+
+        // Replaying the next undone move keeps the rest of the redo line. Moves from
+        // parsers may lack the capture flags, so compare squares and promotion only.
+        let replays_undone = self.undone.last().is_some_and(|m| {
+            m.from() == chess_move.from() && m.to() == chess_move.to() && m.promotion() == chess_move.promotion()
+        });
+        if replays_undone {
+            self.undone.pop();
+        } else {
+            self.undone.clear();
+        }
+
         Ok(())
     }
     
+    /// Takes back the last move; it stays available to `redo_move` until another move is made.
+    pub fn undo_move(&mut self) -> Option<Move> {
+        let record = self.history.pop()?;
+        let (from, to) = (record.chess_move.from(), record.chess_move.to());
+
+        self.pieces.remove(&to);
+        self.pieces.insert(from, record.piece);
+        if record.piece.piece_type == PieceType::King && (to.file as i8 - from.file as i8).abs() == 2 {
+            let (rook_from, rook_to) = if to.file > from.file { (7, 5) } else { (0, 3) };
+            if let Some(rook) = self.pieces.remove(&Position { file: rook_to, rank: from.rank }) {
+                self.pieces.insert(Position { file: rook_from, rank: from.rank }, rook);
+            }
+        }
+        if let Some((square, captured)) = record.captured {
+            self.pieces.insert(square, captured);
+        }
+
+        self.castling_rights = record.castling_rights;
+        self.en_passant_target = record.en_passant_target;
+        self.halfmove_clock = record.halfmove_clock;
+        if self.current_player == Player::White {
+            self.move_count -= 1;
+        }
+        self.current_player = self.current_player.opposite();
+        self.position_history.pop();
+
+        self.undone.push(record.chess_move.clone());
+        Some(record.chess_move)
+    }
+
+    /// Replays the most recently undone move.
+    pub fn redo_move(&mut self) -> Option<Move> {
+        let chess_move = self.undone.last()?.clone();
+        self.make_move(&chess_move).ok()?;
+        Some(chess_move)
+    }
+
+    /// Moves played since the board was set up, oldest first.
+    pub fn move_history(&self) -> Vec<Move> {
+        self.history.iter().map(|record| record.chess_move.clone()).collect()
+    }
+
+    /// Pieces captured since the board was set up, oldest first.
+    pub fn captured_pieces(&self) -> Vec<Piece> {
+        self.history.iter().filter_map(|record| record.captured.map(|(_, piece)| piece)).collect()
+    }
+
+    /// The position the board was set up with, before any of its moves.
+    pub fn starting_fen(&self) -> String {
+        let mut board = self.clone();
+        while board.undo_move().is_some() {}
+        board.to_fen()
+    }
+
+    fn is_en_passant_capture(&self, piece: Piece, chess_move: &Move) -> bool {
+        piece.piece_type == PieceType::Pawn
+            && Some(chess_move.to()) == self.en_passant_target
+            && chess_move.from().file != chess_move.to().file
+    }
+
+    // Moves pieces only: handles en passant removal, the castling rook and promotion.
+    fn move_pieces(
+        pieces: &mut HashMap<Position, Piece>,
+        chess_move: &Move,
+        en_passant_target: Option<Position>,
+    ) {
+        let mut piece = match pieces.remove(&chess_move.from()) {
+            Some(piece) => piece,
+            None => return,
+        };
+        let (from, to) = (chess_move.from(), chess_move.to());
+
+        if piece.piece_type == PieceType::Pawn && Some(to) == en_passant_target && from.file != to.file {
+            pieces.remove(&Position { file: to.file, rank: from.rank });
+        }
+
+        if piece.piece_type == PieceType::King && (to.file as i8 - from.file as i8).abs() == 2 {
+            let (rook_from, rook_to) = if to.file > from.file { (7, 5) } else { (0, 3) };
+            if let Some(rook) = pieces.remove(&Position { file: rook_from, rank: from.rank }) {
+                pieces.insert(Position { file: rook_to, rank: from.rank }, rook);
+            }
+        }
+
+        if let Some(promotion_type) = chess_move.promotion() {
+            if piece.piece_type == PieceType::Pawn {
+                piece.piece_type = promotion_type;
+            }
+        }
+
+        pieces.insert(to, piece);
+    }
+
     pub fn is_legal_move(&self, chess_move: &Move) -> bool {
-        if let Some(piece) = self.piece_at(chess_move.from()) {
-            if piece.player != self.current_player {
-                return false;
+        self.find_legal_move(chess_move.from(), chess_move.to(), chess_move.promotion()).is_some()
+    }
+
+    /// Returns the fully flagged legal move matching the given squares, if any.
+    pub fn find_legal_move(
+        &self,
+        from: Position,
+        to: Position,
+        promotion: Option<PieceType>,
+    ) -> Option<Move> {
+        match self.piece_at(from) {
+            Some(piece) if piece.player == self.current_player => {}
+            _ => return None,
+        }
+
+        self.get_legal_moves()
+            .into_iter()
+            .find(|m| m.from() == from && m.to() == to && m.promotion() == promotion)
+    }
+
+    pub fn get_legal_moves(&self) -> Vec<Move> {
+        let player = self.current_player;
+        self.pseudo_legal_moves()
+            .into_iter()
+            .filter(|chess_move| {
+                let mut pieces = self.pieces.clone();
+                Self::move_pieces(&mut pieces, chess_move, self.en_passant_target);
+                match Self::find_king(&pieces, player) {
+                    Some(king) => !Self::is_attacked(&pieces, king, player.opposite()),
+                    None => true,
+                }
+            })
+            .collect()
+    }
+
+    fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let player = self.current_player;
+
+        for from in Position::all() {
+            let piece = match self.piece_at(from) {
+                Some(piece) if piece.player == player => *piece,
+                _ => continue,
+            };
+
+            match piece.piece_type {
+                PieceType::Pawn => self.pawn_moves(from, &mut moves),
+                PieceType::Knight => self.step_moves(from, &KNIGHT_OFFSETS, &mut moves),
+                PieceType::King => {
+                    self.step_moves(from, &KING_OFFSETS, &mut moves);
+                    self.castling_moves(from, &mut moves);
+                }
+                PieceType::Rook => self.slide_moves(from, &ROOK_DIRECTIONS, &mut moves),
+                PieceType::Bishop => self.slide_moves(from, &BISHOP_DIRECTIONS, &mut moves),
+                PieceType::Queen => {
+                    self.slide_moves(from, &ROOK_DIRECTIONS, &mut moves);
+                    self.slide_moves(from, &BISHOP_DIRECTIONS, &mut moves);
+                }
             }
-            
-            if let Some(dest_piece) = self.piece_at(chess_move.to()) {
-                if dest_piece.player == piece.player {
-                    return false;
+        }
+
+        moves
+    }
+
+    fn pawn_moves(&self, from: Position, moves: &mut Vec<Move>) {
+        let (direction, start_rank, promotion_rank) = match self.current_player {
+            Player::White => (1, 1, 7),
+            Player::Black => (-1, 6, 0),
+        };
+
+        let push_with_promotions = |moves: &mut Vec<Move>, to: Position, capture: bool| {
+            if to.rank == promotion_rank {
+                for promotion in PROMOTION_TYPES {
+                    let chess_move = Move::new(from, to, Some(promotion));
+                    moves.push(if capture { chess_move.with_capture() } else { chess_move });
+                }
+            } else {
+                let chess_move = Move::new(from, to, None);
+                moves.push(if capture { chess_move.with_capture() } else { chess_move });
+            }
+        };
+
+        if let Some(one_step) = from.offset(0, direction) {
+            if self.piece_at(one_step).is_none() {
+                push_with_promotions(moves, one_step, false);
+                if from.rank == start_rank {
+                    if let Some(two_step) = one_step.offset(0, direction) {
+                        if self.piece_at(two_step).is_none() {
+                            moves.push(Move::new(from, two_step, None));
+                        }
+                    }
+                }
+            }
+        }
+
+        for file_delta in [-1, 1] {
+            if let Some(to) = from.offset(file_delta, direction) {
+                match self.piece_at(to) {
+                    Some(target) if target.player != self.current_player => {
+                        push_with_promotions(moves, to, true);
+                    }
+                    None if Some(to) == self.en_passant_target => {
+                        moves.push(Move::new(from, to, None).with_capture().with_en_passant());
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn step_moves(&self, from: Position, offsets: &[(i8, i8)], moves: &mut Vec<Move>) {
+        for &(file_delta, rank_delta) in offsets {
+            if let Some(to) = from.offset(file_delta, rank_delta) {
+                match self.piece_at(to) {
+                    None => moves.push(Move::new(from, to, None)),
+                    Some(target) if target.player != self.current_player => {
+                        moves.push(Move::new(from, to, None).with_capture());
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn slide_moves(&self, from: Position, directions: &[(i8, i8)], moves: &mut Vec<Move>) {
+        for &(file_delta, rank_delta) in directions {
+            let mut current = from;
+            while let Some(to) = current.offset(file_delta, rank_delta) {
+                match self.piece_at(to) {
+                    None => moves.push(Move::new(from, to, None)),
+                    Some(target) => {
+                        if target.player != self.current_player {
+                            moves.push(Move::new(from, to, None).with_capture());
+                        }
+                        break;
+                    }
                 }
+                current = to;
+            }
+        }
+    }
+
+    fn castling_moves(&self, from: Position, moves: &mut Vec<Move>) {
+        let player = self.current_player;
+        let home_rank = if player == Player::White { 0 } else { 7 };
+        if from != (Position { file: 4, rank: home_rank }) {
+            return;
+        }
+        let opponent = player.opposite();
+        if Self::is_attacked(&self.pieces, from, opponent) {
+            return;
+        }
+
+        for kingside in [true, false] {
+            if !self.castling_rights.has(player, kingside) {
+                continue;
+            }
+            let (rook_file, empty_files, king_path): (u8, &[u8], [u8; 2]) = if kingside {
+                (7, &[5, 6], [5, 6])
+            } else {
+                (0, &[1, 2, 3], [3, 2])
+            };
+
+            let rook_square = Position { file: rook_file, rank: home_rank };
+            if self.piece_at(rook_square) != Some(&Piece::new(PieceType::Rook, player)) {
+                continue;
+            }
+            if empty_files
+                .iter()
+                .any(|&file| self.piece_at(Position { file, rank: home_rank }).is_some())
+            {
+                continue;
+            }
+            if king_path
+                .iter()
+                .any(|&file| Self::is_attacked(&self.pieces, Position { file, rank: home_rank }, opponent))
+            {
+                continue;
             }
             
+            let to = Position { file: king_path[1], rank: home_rank };
+            moves.push(Move::new(from, to, None).with_castling());
+        }
+    }
+
+    fn find_king(pieces: &HashMap<Position, Piece>, player: Player) -> Option<Position> {
+        pieces
+            .iter()
+            .find(|(_, piece)| piece.piece_type == PieceType::King && piece.player == player)
+            .map(|(pos, _)| *pos)
+    }
+
+    fn is_attacked(pieces: &HashMap<Position, Piece>, square: Position, by: Player) -> bool {
+        let has = |pos: Option<Position>, types: &[PieceType]| {
+            pos.and_then(|p| pieces.get(&p))
+                .is_some_and(|piece| piece.player == by && types.contains(&piece.piece_type))
+        };
+
+        let pawn_rank_delta = if by == Player::White { -1 } else { 1 };
+        if has(square.offset(-1, pawn_rank_delta), &[PieceType::Pawn])
+            || has(square.offset(1, pawn_rank_delta), &[PieceType::Pawn])
+        {
             return true;
         }
+
+        if KNIGHT_OFFSETS.iter().any(|&(f, r)| has(square.offset(f, r), &[PieceType::Knight])) {
+            return true;
+        }
+        if KING_OFFSETS.iter().any(|&(f, r)| has(square.offset(f, r), &[PieceType::King])) {
+            return true;
+        }
+
+        let sliders = [
+            (&ROOK_DIRECTIONS, [PieceType::Rook, PieceType::Queen]),
+            (&BISHOP_DIRECTIONS, [PieceType::Bishop, PieceType::Queen]),
+        ];
+        for (directions, types) in sliders {
+            for &(file_delta, rank_delta) in directions.iter() {
+                let mut current = square;
+                while let Some(next) = current.offset(file_delta, rank_delta) {
+                    if let Some(piece) = pieces.get(&next) {
+                        if piece.player == by && types.contains(&piece.piece_type) {
+                            return true;
+                        }
+                        break;
+                    }
+                    current = next;
+                }
+            }
+        }
         
         false
     }
     
-    pub fn get_legal_moves(&self) -> Vec<Move> {
-        vec![]
-    }
-    
     pub fn game_state(&self) -> GameState {
+        if self.get_legal_moves().is_empty() {
+            if self.is_in_check(self.current_player) {
+                return GameState::Checkmate;
+            }
+            return GameState::Stalemate;
+        }
+    
+        // Only the automatic draws; threefold and fifty-move draws must be claimed.
+        if self.is_insufficient_material() || self.halfmove_clock >= 150 || self.repetition_count() >= 5 {
+            return GameState::Draw;
+        }
+
         GameState::InProgress
     }
     
-    pub fn is_in_check(&self, _player: Player) -> bool {
-        false
+    pub fn is_in_check(&self, player: Player) -> bool {
+        match Self::find_king(&self.pieces, player) {
+            Some(king) => Self::is_attacked(&self.pieces, king, player.opposite()),
+            None => false,
+        }
+    }
+
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut minors = Vec::new();
+        for (pos, piece) in &self.pieces {
+            match piece.piece_type {
+                PieceType::King => {}
+                PieceType::Knight | PieceType::Bishop => minors.push((*pos, piece.piece_type)),
+                _ => return false,
+            }
+        }
+
+        if minors.len() <= 1 {
+            return true;
+        }
+
+        // Any number of bishops confined to one square color cannot mate.
+        let first_color = (minors[0].0.file + minors[0].0.rank) % 2;
+        minors
+            .iter()
+            .all(|(pos, piece_type)| *piece_type == PieceType::Bishop && (pos.file + pos.rank) % 2 == first_color)
+    }
+
+    /// How many times the current position has occurred, including now.
+    pub fn repetition_count(&self) -> usize {
+        match self.position_history.last() {
+            Some(current) => self.position_history.iter().filter(|key| *key == current).count(),
+            None => 1,
+        }
+    }
+
+    pub fn can_castle(&self, player: Player, kingside: bool) -> bool {
+        self.castling_rights.has(player, kingside)
+    }
+
+    /// The en passant square, but only when a pawn can actually capture there.
+    pub fn en_passant_square(&self) -> Option<Position> {
+        // The cheap pseudo-legal check rules out most positions before the legal one.
+        let captures = |moves: Vec<Move>| moves.iter().any(|m| m.is_en_passant());
+        self.en_passant_target
+            .filter(|_| captures(self.pseudo_legal_moves()) && captures(self.get_legal_moves()))
+    }
+
+    // Placement, side to move, castling rights and a *capturable* en passant square.
+    fn repetition_key(&self) -> String {
+        let fen = self.to_fen();
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let en_passant = match self.en_passant_square() {
+            Some(_) => fields[3],
+            None => "-",
+        };
+        format!("{} {} {} {}", fields[0], fields[1], fields[2], en_passant)
     }
     
     pub fn to_fen(&self) -> String {
@@ -153,21 +747,7 @@ impl Board {
                         fen.push_str(&empty_count.to_string());
                         empty_count = 0;
                     }
-                    let symbol = match (piece.piece_type, piece.player) {
-                        (PieceType::King, Player::White) => 'K',
-                        (PieceType::Queen, Player::White) => 'Q',
-                        (PieceType::Rook, Player::White) => 'R',
-                        (PieceType::Bishop, Player::White) => 'B',
-                        (PieceType::Knight, Player::White) => 'N',
-                        (PieceType::Pawn, Player::White) => 'P',
-                        (PieceType::King, Player::Black) => 'k',
-                        (PieceType::Queen, Player::Black) => 'q',
-                        (PieceType::Rook, Player::Black) => 'r',
-                        (PieceType::Bishop, Player::Black) => 'b',
-                        (PieceType::Knight, Player::Black) => 'n',
-                        (PieceType::Pawn, Player::Black) => 'p',
-                    };
-                    fen.push(symbol);
+                    fen.push(piece.fen_char());
                 } else {
                     empty_count += 1;
                 }
@@ -203,4 +783,174 @@ impl Board {
         
         fen
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    fn perft(board: &mut Board, depth: u32) -> u64 {
+        let moves = board.get_legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes = 0;
+        for chess_move in moves {
+            board.make_move(&chess_move).unwrap();
+            nodes += perft(board, depth - 1);
+            board.undo_move();
+        }
+        nodes
+    }
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    fn play(board: &mut Board, uci: &str) {
+        let m = Move::from_uci(uci).unwrap();
+        let chess_move = board.find_legal_move(m.from(), m.to(), m.promotion()).unwrap();
+        board.make_move(&chess_move).unwrap();
+    }
+
+    // Node counts from the Chess Programming Wiki's perft results.
+    #[test]
+    fn perft_matches_the_reference_positions() {
+        let positions = [
+            (STARTING_FEN, 3, 8_902),
+            (KIWIPETE, 3, 97_862),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43_238),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 3, 9_467),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 3, 62_379),
+        ];
+        for (fen, depth, nodes) in positions {
+            assert_eq!(perft(&mut board(fen), depth), nodes, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn fen_round_trips() {
+        for fen in [STARTING_FEN, KIWIPETE, "8/8/8/8/8/8/8/K6k b - - 99 120"] {
+            assert_eq!(board(fen).to_fen(), fen);
+        }
+        assert_eq!(Board::new().to_fen(), STARTING_FEN);
+        assert!(Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1").is_err());
+    }
+
+    #[test]
+    fn castling_rights_follow_king_and_rook_moves() {
+        let mut position = board(KIWIPETE);
+        play(&mut position, "h1g1");
+        assert!(!position.can_castle(Player::White, true));
+        assert!(position.can_castle(Player::White, false));
+        play(&mut position, "e8d8");
+        assert!(!position.can_castle(Player::Black, true) && !position.can_castle(Player::Black, false));
+        assert!(position.to_fen().starts_with("r2k3r/") && position.to_fen().contains(" w Q - "));
+
+        // Capturing a rook on its home square takes away that side's right.
+        let mut position = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        play(&mut position, "a1a8");
+        assert!(!position.can_castle(Player::White, false) && !position.can_castle(Player::Black, false));
+
+        // Castling moves the rook too, and undoing puts both back.
+        let mut position = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        play(&mut position, "e1g1");
+        assert_eq!(position.to_fen(), "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1");
+        position.undo_move();
+        assert_eq!(position.to_fen(), "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+
+        // No castling out of, through or into check.
+        let position = board("r3k2r/8/8/8/8/8/5r2/R3K2R w KQkq - 0 1");
+        let castles = |position: &Board| {
+            position.get_legal_moves().into_iter().filter(|m| m.from().file == 4 && (m.to().file as i8 - 4).abs() == 2).count()
+        };
+        assert_eq!(castles(&position), 1);
+        assert_eq!(castles(&board("r3k2r/8/8/8/8/8/4r3/R3K2R w KQkq - 0 1")), 0);
+    }
+
+    #[test]
+    fn en_passant_captures_and_undoes() {
+        let mut position = board("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1");
+        play(&mut position, "e2e4");
+        assert_eq!(position.en_passant_square(), Position::from_algebraic("e3").ok());
+        assert!(position.to_fen().contains(" e3 "));
+        play(&mut position, "d4e3");
+        assert_eq!(position.to_fen(), "4k3/8/8/8/8/4p3/8/4K3 w - - 0 2");
+        position.undo_move();
+        assert_eq!(position.to_fen(), "4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1");
+
+        // The capture is illegal when it would expose the king along the rank.
+        let position = board("8/8/8/8/k2pP2R/8/8/4K3 b - e3 0 1");
+        assert!(position.get_legal_moves().iter().all(|m| !m.is_en_passant()));
+        assert_eq!(position.en_passant_square(), None);
+    }
+
+    #[test]
+    fn promotions_offer_every_piece() {
+        let mut position = board("8/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+        let promotions: Vec<_> = position.get_legal_moves().into_iter().filter_map(|m| m.promotion()).collect();
+        assert_eq!(promotions.len(), 4);
+        play(&mut position, "e7e8n");
+        assert_eq!(position.piece_at(Position::from_algebraic("e8").unwrap()), Some(&Piece::new(PieceType::Knight, Player::White)));
+        position.undo_move();
+        assert_eq!(position.piece_at(Position::from_algebraic("e7").unwrap()), Some(&Piece::new(PieceType::Pawn, Player::White)));
+        assert!(position.find_legal_move(Position::from_algebraic("e7").unwrap(), Position::from_algebraic("e8").unwrap(), None).is_none());
+    }
+
+    #[test]
+    fn undo_and_redo_keep_the_history() {
+        let mut position = Board::new();
+        for uci in ["e2e4", "e7e5", "g1f3"] {
+            play(&mut position, uci);
+        }
+        let fen = position.to_fen();
+        assert_eq!(position.undo_move().map(|m| m.to_uci()), Some("g1f3".to_string()));
+        assert_eq!(position.redo_move().map(|m| m.to_uci()), Some("g1f3".to_string()));
+        assert_eq!(position.to_fen(), fen);
+        position.undo_move();
+        play(&mut position, "b1c3");
+        assert!(position.redo_move().is_none());
+        assert_eq!(position.move_history().len(), 3);
+    }
+
+    #[test]
+    fn captures_and_starting_position() {
+        let fen = "4k3/8/8/3p4/4P3/8/8/R3K3 w Q - 0 1";
+        let mut position = board(fen);
+        for uci in ["e4d5", "e8d7", "a1a7"] {
+            play(&mut position, uci);
+        }
+        assert_eq!(position.captured_pieces(), [Piece::new(PieceType::Pawn, Player::Black)]);
+        assert_eq!(position.starting_fen(), fen);
+        assert_eq!(position.move_history().len(), 3);
+        assert_eq!(Board::new().starting_fen(), STARTING_FEN);
+    }
+
+    #[test]
+    fn automatic_draws() {
+        let mut position = Board::new();
+        for _ in 0..4 {
+            for uci in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                play(&mut position, uci);
+            }
+        }
+        assert_eq!(position.repetition_count(), 5);
+        assert_eq!(position.game_state(), GameState::Draw);
+
+        assert_eq!(board("4k3/8/8/8/8/8/8/R3K3 w - - 150 100").game_state(), GameState::Draw);
+        assert_eq!(board("4k3/8/8/8/8/8/8/R3K3 w - - 149 100").game_state(), GameState::InProgress);
+
+        assert!(!board("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").is_insufficient_material());
+        assert!(board("4k3/8/8/8/8/8/8/2B1K1B1 w - - 0 1").is_insufficient_material());
+        assert!(board("4k3/8/8/8/8/8/8/4KN2 w - - 0 1").is_insufficient_material());
+        assert!(!board("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1").is_insufficient_material());
+    }
+
+    #[test]
+    fn mate_and_stalemate() {
+        assert_eq!(board("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").game_state(), GameState::Checkmate);
+        assert_eq!(board("7k/8/6QK/8/8/8/8/8 b - - 0 1").game_state(), GameState::Stalemate);
+    }
+}
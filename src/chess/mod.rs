@@ -2,8 +2,9 @@ pub mod board;
 pub mod moves;
 pub mod pieces;
 pub mod position;
+pub mod san;
 
-pub use board::{Board, GameState};
+pub use board::{Board, GameResult, GameState, STARTING_FEN};
 pub use moves::Move;
 pub use pieces::{Piece, PieceType, Player};
 pub use position::Position;
\ No newline at end of file
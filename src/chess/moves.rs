@@ -1,4 +1,5 @@
 use super::{PieceType, Position};
+use anyhow::{anyhow, Result};
 use std::fmt;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -23,6 +24,27 @@ impl Move {
         }
     }
     
+    pub fn from_uci(uci_move: &str) -> Result<Self> {
+        if uci_move.len() < 4 || uci_move.len() > 5 || !uci_move.is_ascii() {
+            return Err(anyhow!("Invalid UCI move: {}", uci_move));
+        }
+        
+        let from = Position::from_algebraic(&uci_move[0..2])?;
+        let to = Position::from_algebraic(&uci_move[2..4])?;
+        
+        let promotion = match uci_move.chars().nth(4) {
+            Some(c) => match PieceType::from_char(c) {
+                Some(PieceType::King) | Some(PieceType::Pawn) | None => {
+                    return Err(anyhow!("Invalid UCI promotion: {}", uci_move));
+                }
+                piece_type => piece_type,
+            },
+            None => None,
+        };
+        
+        Ok(Move::new(from, to, promotion))
+    }
+    
     pub fn with_capture(mut self) -> Self {
         self.is_capture = true;
         self
@@ -50,6 +72,10 @@ impl Move {
         self.promotion
     }
     
+    pub fn is_en_passant(&self) -> bool {
+        self.is_en_passant
+    }
+    
     pub fn to_algebraic(&self) -> String {
         if self.is_castling {
             if self.to.file == 6 {
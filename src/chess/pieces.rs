@@ -25,6 +25,42 @@ pub enum PieceType {
     King,
 }
 
+impl PieceType {
+    pub fn from_char(c: char) -> Option<PieceType> {
+        match c.to_ascii_uppercase() {
+            'P' => Some(PieceType::Pawn),
+            'R' => Some(PieceType::Rook),
+            'N' => Some(PieceType::Knight),
+            'B' => Some(PieceType::Bishop),
+            'Q' => Some(PieceType::Queen),
+            'K' => Some(PieceType::King),
+            _ => None,
+        }
+    }
+    
+    pub fn to_char(self) -> char {
+        match self {
+            PieceType::Pawn => 'P',
+            PieceType::Rook => 'R',
+            PieceType::Knight => 'N',
+            PieceType::Bishop => 'B',
+            PieceType::Queen => 'Q',
+            PieceType::King => 'K',
+        }
+    }
+    
+    /// Conventional material value, in pawns.
+    pub fn value(self) -> u32 {
+        match self {
+            PieceType::Pawn => 1,
+            PieceType::Knight | PieceType::Bishop => 3,
+            PieceType::Rook => 5,
+            PieceType::Queen => 9,
+            PieceType::King => 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Piece {
     pub piece_type: PieceType,
@@ -36,6 +72,18 @@ impl Piece {
         Piece { piece_type, player }
     }
     
+    pub fn from_fen_char(c: char) -> Option<Piece> {
+        let player = if c.is_ascii_uppercase() { Player::White } else { Player::Black };
+        PieceType::from_char(c).map(|piece_type| Piece::new(piece_type, player))
+    }
+    
+    pub fn fen_char(&self) -> char {
+        match self.player {
+            Player::White => self.piece_type.to_char(),
+            Player::Black => self.piece_type.to_char().to_ascii_lowercase(),
+        }
+    }
+    
     pub fn unicode_symbol(&self) -> char {
         match (self.player, self.piece_type) {
             (Player::White, PieceType::King) => '♔',
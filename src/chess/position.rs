@@ -36,14 +36,24 @@ impl Position {
         Ok(Position { file, rank })
     }
     
-    pub fn to_algebraic(&self) -> String {
+    pub fn to_algebraic(self) -> String {
         let file_char = (b'a' + self.file) as char;
         let rank_char = (b'1' + self.rank) as char;
         format!("{}{}", file_char, rank_char)
     }
     
-    pub fn is_valid(&self) -> bool {
-        self.file <= 7 && self.rank <= 7
+    pub fn offset(self, file_delta: i8, rank_delta: i8) -> Option<Position> {
+        let file = self.file as i8 + file_delta;
+        let rank = self.rank as i8 + rank_delta;
+        if (0..8).contains(&file) && (0..8).contains(&rank) {
+            Some(Position { file: file as u8, rank: rank as u8 })
+        } else {
+            None
+        }
+    }
+    
+    pub fn all() -> impl Iterator<Item = Position> {
+        (0..8).flat_map(|rank| (0..8).map(move |file| Position { file, rank }))
     }
 }
 
@@ -0,0 +1,241 @@
+use super::{Board, GameState, Move, PieceType, Position};
+use anyhow::{anyhow, Result};
+
+impl Board {
+    /// Renders a legal move in Standard Algebraic Notation, e.g. `Nbd7`, `exd5`, `e8=Q+`.
+    pub fn to_san(&self, chess_move: &Move) -> String {
+        let piece = match self.piece_at(chess_move.from()) {
+            Some(piece) => *piece,
+            None => return chess_move.to_uci(),
+        };
+        let (from, to) = (chess_move.from(), chess_move.to());
+
+        let mut san = String::new();
+        if piece.piece_type == PieceType::King && (to.file as i8 - from.file as i8).abs() == 2 {
+            san.push_str(if to.file > from.file { "O-O" } else { "O-O-O" });
+        } else {
+            let is_capture = self.piece_at(to).is_some()
+                || (piece.piece_type == PieceType::Pawn && from.file != to.file);
+
+            if piece.piece_type == PieceType::Pawn {
+                if is_capture {
+                    san.push((b'a' + from.file) as char);
+                }
+            } else {
+                san.push(piece.piece_type.to_char());
+                san.push_str(&self.disambiguation(chess_move, piece.piece_type));
+            }
+
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&to.to_algebraic());
+
+            if let Some(promotion) = chess_move.promotion() {
+                san.push('=');
+                san.push(promotion.to_char());
+            }
+        }
+
+        let mut after = self.clone();
+        if after.make_move(chess_move).is_ok() && after.is_in_check(after.current_player()) {
+            san.push(if after.game_state() == GameState::Checkmate { '#' } else { '+' });
+        }
+
+        san
+    }
+
+    fn disambiguation(&self, chess_move: &Move, piece_type: PieceType) -> String {
+        let from = chess_move.from();
+        let rivals: Vec<Position> = self
+            .get_legal_moves()
+            .into_iter()
+            .filter(|m| m.to() == chess_move.to() && m.from() != from)
+            .filter(|m| self.piece_at(m.from()).map(|p| p.piece_type) == Some(piece_type))
+            .map(|m| m.from())
+            .collect();
+
+        if rivals.is_empty() {
+            String::new()
+        } else if rivals.iter().all(|p| p.file != from.file) {
+            ((b'a' + from.file) as char).to_string()
+        } else if rivals.iter().all(|p| p.rank != from.rank) {
+            ((b'1' + from.rank) as char).to_string()
+        } else {
+            from.to_algebraic()
+        }
+    }
+
+    /// Parses a SAN move (tolerating missing `x`, check marks and annotations) into a legal move.
+    pub fn parse_san(&self, san: &str) -> Result<Move> {
+        let cleaned: String = san
+            .trim()
+            .trim_end_matches(['+', '#', '!', '?'])
+            .chars()
+            .filter(|c| *c != 'x' && *c != '-' && *c != ':')
+            .collect();
+
+        let legal_moves = self.get_legal_moves();
+
+        let castle_file = match cleaned.as_str() {
+            "OO" | "00" => Some(6),
+            "OOO" | "000" => Some(2),
+            _ => None,
+        };
+        if let Some(file) = castle_file {
+            return legal_moves
+                .into_iter()
+                .find(|m| {
+                    m.to().file == file
+                        && self.piece_at(m.from()).map(|p| p.piece_type) == Some(PieceType::King)
+                        && (m.to().file as i8 - m.from().file as i8).abs() == 2
+                })
+                .ok_or_else(|| anyhow!("Castling is not legal here: {}", san));
+        }
+
+        let mut chars: Vec<char> = cleaned.chars().collect();
+
+        let mut promotion = None;
+        if let Some(&last) = chars.last() {
+            if last.is_ascii_alphabetic() && chars.len() > 2 {
+                if let Some(piece_type) = PieceType::from_char(last) {
+                    if last.is_ascii_uppercase() || chars[chars.len() - 2] == '=' {
+                        promotion = Some(piece_type);
+                        chars.pop();
+                        if chars.last() == Some(&'=') {
+                            chars.pop();
+                        }
+                    }
+                }
+            }
+        }
+
+        let piece_type = match chars.first() {
+            Some(&c) if "KQRBN".contains(c) => {
+                chars.remove(0);
+                PieceType::from_char(c).unwrap_or(PieceType::Pawn)
+            }
+            _ => PieceType::Pawn,
+        };
+
+        if chars.len() < 2 {
+            return Err(anyhow!("Could not parse move: '{}'", san));
+        }
+        let destination: String = chars[chars.len() - 2..].iter().collect();
+        let to = Position::from_algebraic(&destination)?;
+
+        let mut from_file = None;
+        let mut from_rank = None;
+        for &c in &chars[..chars.len() - 2] {
+            match c {
+                'a'..='h' => from_file = Some(c as u8 - b'a'),
+                '1'..='8' => from_rank = Some(c as u8 - b'1'),
+                _ => return Err(anyhow!("Could not parse move: '{}'", san)),
+            }
+        }
+
+        let candidates: Vec<Move> = legal_moves
+            .into_iter()
+            .filter(|m| {
+                m.to() == to
+                    && m.promotion() == promotion
+                    && self.piece_at(m.from()).map(|p| p.piece_type) == Some(piece_type)
+                    && from_file.is_none_or(|file| m.from().file == file)
+                    && from_rank.is_none_or(|rank| m.from().rank == rank)
+            })
+            .collect();
+
+        match candidates.len() {
+            1 => Ok(candidates.into_iter().next().unwrap()),
+            0 => Err(anyhow!("Illegal move: '{}'", san)),
+            _ => Err(anyhow!("Ambiguous move: '{}'", san)),
+        }
+    }
+
+    /// Parses typed input as SAN or, failing that, as a UCI move such as `e2e4` or `a7a8q`.
+    pub fn parse_move(&self, text: &str) -> Result<Move> {
+        let san_error = match self.parse_san(text) {
+            Ok(chess_move) => return Ok(chess_move),
+            Err(e) => e,
+        };
+        Move::from_uci(&text.trim().to_lowercase())
+            .ok()
+            .and_then(|m| self.find_legal_move(m.from(), m.to(), m.promotion()))
+            .ok_or(san_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::STARTING_FEN;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    fn san(fen: &str, uci: &str) -> String {
+        let position = board(fen);
+        let m = Move::from_uci(uci).unwrap();
+        position.to_san(&position.find_legal_move(m.from(), m.to(), m.promotion()).unwrap())
+    }
+
+    #[test]
+    fn every_legal_move_round_trips() {
+        let positions = [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/7k/8/8/8/Q1Q5/8/Q1Q1K3 w - - 0 1",
+        ];
+        for fen in positions {
+            let position = board(fen);
+            for chess_move in position.get_legal_moves() {
+                let text = position.to_san(&chess_move);
+                assert_eq!(position.parse_san(&text).ok(), Some(chess_move), "{} in {}", text, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn moves_print_in_standard_notation() {
+        assert_eq!(san(STARTING_FEN, "g1f3"), "Nf3");
+        assert_eq!(san("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1g1"), "O-O");
+        assert_eq!(san("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1c1"), "O-O-O");
+        assert_eq!(san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), "exd6");
+        assert_eq!(san("8/4P3/8/8/8/8/k7/4K3 w - - 0 1", "e7e8q"), "e8=Q");
+        assert_eq!(san("3k4/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8r"), "e8=R+");
+        assert_eq!(san("7k/8/6K1/8/8/8/8/Q7 w - - 0 1", "a1a8"), "Qa8#");
+        // File, then rank, then both when neither alone is unique.
+        assert_eq!(san("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "b1d2"), "Nbd2");
+        assert_eq!(san("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3"), "R1a3");
+        assert_eq!(san("8/7k/8/8/8/Q1Q5/8/Q1Q1K3 w - - 0 1", "a1b2"), "Qa1b2");
+    }
+
+    #[test]
+    fn parsing_tolerates_common_variants() {
+        let position = board("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let expected = |uci: &str| Move::from_uci(uci).ok().and_then(|m| position.find_legal_move(m.from(), m.to(), m.promotion()));
+        assert_eq!(position.parse_san("0-0").ok(), expected("e1g1"));
+        assert_eq!(position.parse_san("O-O-O").ok(), expected("e1c1"));
+        assert_eq!(position.parse_san("Bxa6!?").ok(), expected("e2a6"));
+        assert_eq!(position.parse_san("Ba6").ok(), expected("e2a6"));
+        assert_eq!(position.parse_move("e2a6").ok(), expected("e2a6"));
+        assert_eq!(position.parse_move("E2A6").ok(), expected("e2a6"));
+
+        let knights = board("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1");
+        assert!(knights.parse_san("Nd2").unwrap_err().to_string().starts_with("Ambiguous"));
+        assert!(position.parse_san("Ke3").unwrap_err().to_string().starts_with("Illegal"));
+        assert!(position.parse_move("hello").is_err());
+    }
+
+    #[test]
+    fn promotions_need_a_piece() {
+        let position = board("8/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+        assert_eq!(position.parse_san("e8=N").unwrap().promotion(), Some(PieceType::Knight));
+        assert_eq!(position.parse_san("e8Q").unwrap().promotion(), Some(PieceType::Queen));
+        assert_eq!(position.parse_move("e7e8r").unwrap().promotion(), Some(PieceType::Rook));
+        assert!(position.parse_san("e8").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::book::polyglot_key;
+use crate::chess::Board;
+use crate::pgn::PgnGame;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// ECO code, opening, variation and moves of each named line, tab separated.
+const ECO_TABLE: &str = include_str!("eco.tsv");
+
+#[derive(Debug)]
+pub struct Opening {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub variation: Option<&'static str>,
+}
+
+impl fmt::Display for Opening {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.code, self.name)?;
+        if let Some(variation) = self.variation {
+            write!(f, ": {}", variation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Named openings keyed by the position their line ends in, so transpositions are recognised.
+fn openings() -> &'static HashMap<u64, Opening> {
+    static OPENINGS: OnceLock<HashMap<u64, Opening>> = OnceLock::new();
+    OPENINGS.get_or_init(|| {
+        let mut openings = HashMap::new();
+        for line in ECO_TABLE.lines().filter(|line| !line.trim().is_empty()) {
+            let mut fields = line.split('\t');
+            let (Some(code), Some(name), Some(variation), Some(moves)) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            if let Some(board) = play_line(moves) {
+                let variation = (!variation.is_empty()).then_some(variation);
+                openings.insert(polyglot_key(&board), Opening { code, name, variation });
+            }
+        }
+        openings
+    })
+}
+
+fn play_line(moves: &str) -> Option<Board> {
+    let mut board = Board::new();
+    for san in moves.split_whitespace().filter(|token| !token.ends_with('.')) {
+        let chess_move = board.parse_san(san).ok()?;
+        board.make_move(&chess_move).ok()?;
+    }
+    Some(board)
+}
+
+/// The most specific named opening the game passed through on its way to `board`.
+pub fn classify(board: &Board) -> Option<&'static Opening> {
+    let openings = openings();
+    let mut position = board.clone();
+    loop {
+        if let Some(opening) = openings.get(&polyglot_key(&position)) {
+            return Some(opening);
+        }
+        position.undo_move()?;
+    }
+}
+
+/// Sets the `ECO`, `Opening` and `Variation` tags from the game's mainline.
+pub fn tag_game(game: &mut PgnGame) {
+    let Ok(boards) = game.mainline_boards() else {
+        return;
+    };
+    let openings = openings();
+    let Some(opening) = boards.iter().rev().find_map(|board| openings.get(&polyglot_key(board))) else {
+        return;
+    };
+    game.set_tag("ECO", opening.code);
+    game.set_tag("Opening", opening.name);
+    if let Some(variation) = opening.variation {
+        game.set_tag("Variation", variation);
+    }
+}
@@ -1,49 +1,261 @@
-use crate::chess::{Board, Move, Position};
+use crate::chess::{Board, Move};
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
 use std::io::{BufRead, BufReader, Write};
 use std::process::{Child, Command, Stdio};
-use std::time::Duration;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How an engine invocation is configured: `cmd=stockfish,name=sf,option.Hash=64`.
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    pub command: String,
+    pub name: Option<String>,
+    pub options: Vec<(String, String)>,
+}
+
+impl EngineConfig {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut command = None;
+        let mut name = None;
+        let mut options = Vec::new();
+
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected key=value in engine spec, got '{}'", part))?;
+            match key {
+                "cmd" => command = Some(value.to_string()),
+                "name" => name = Some(value.to_string()),
+                _ => match key.strip_prefix("option.") {
+                    Some(option) => options.push((option.to_string(), value.to_string())),
+                    None => return Err(anyhow!("Unknown engine setting '{}'", key)),
+                },
+            }
+        }
+
+        Ok(EngineConfig {
+            command: command.ok_or_else(|| anyhow!("Engine spec '{}' is missing cmd=", spec))?,
+            name,
+            options,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Score {
+    Centipawns(i32),
+    Mate(i32),
+}
+
+impl Score {
+    /// Flattens mate scores onto the centipawn scale, for thresholds and averages.
+    pub fn as_centipawns(self) -> i32 {
+        match self {
+            Score::Centipawns(cp) => cp,
+            Score::Mate(moves) if moves > 0 => 100_000 - moves,
+            Score::Mate(moves) => -100_000 - moves,
+        }
+    }
+
+    /// The same score from the other side.
+    pub fn negate(self) -> Score {
+        match self {
+            Score::Centipawns(cp) => Score::Centipawns(-cp),
+            Score::Mate(moves) => Score::Mate(-moves),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchInfo {
+    /// 1-based line number when the engine runs with MultiPV.
+    pub multipv: u32,
+    pub depth: u32,
+    pub score: Option<Score>,
+    pub pv: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub info: SearchInfo,
+}
+
+/// Output of a search running in the background.
+#[derive(Debug, Clone)]
+pub enum SearchUpdate {
+    Info(SearchInfo),
+    BestMove(Option<Move>),
+}
+
+#[derive(Debug, Clone)]
+pub enum SearchLimit {
+    MoveTime(Duration),
+    Clock {
+        white_time: Duration,
+        black_time: Duration,
+        white_increment: Duration,
+        black_increment: Duration,
+        moves_to_go: Option<u32>,
+    },
+}
+
+// How long the engine gets to answer `uci` and `isready`.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(60);
+// How long past its move time a search may run before the engine counts as not answering.
+const ANSWER_GRACE: Duration = Duration::from_secs(2);
 
 pub struct StockfishEngine {
     process: Child,
+    // Output lines from a reader thread, so analysis can be polled without blocking.
+    lines: Receiver<String>,
+    name: String,
+    analysing: bool,
+    hung: bool,
 }
 
 impl StockfishEngine {
     pub fn new(engine_path: &str) -> Result<Self> {
-        let process = Command::new(engine_path)
+        let mut process = Command::new(engine_path)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
+            .stderr(Stdio::null())
             .spawn()
             .map_err(|e| anyhow!("Failed to start Stockfish: {}", e))?;
-        
-        let mut engine = StockfishEngine { process };
-        
+
+        let stdout = process
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Failed to capture engine output"))?;
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line.trim_end().to_string()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = StockfishEngine {
+            process,
+            lines,
+            name: engine_path.to_string(),
+            analysing: false,
+            hung: false,
+        };
+
         engine.send_command("uci")?;
-        engine.wait_for_response("uciok")?;
+        for line in engine.read_until("uciok")? {
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            }
+        }
         engine.send_command("isready")?;
         engine.wait_for_response("readyok")?;
-        
+
         Ok(engine)
     }
-    
-    pub fn get_best_move(&mut self, board: &Board, time_limit: Duration) -> Result<Option<Move>> {
-        let fen = board.to_fen();
-        
-        self.send_command(&format!("position fen {}", fen))?;
-        
-        let time_ms = time_limit.as_millis();
-        self.send_command(&format!("go movetime {}", time_ms))?;
-        
-        let best_move = self.read_best_move()?;
-        
-        if let Some(uci_move) = best_move {
-            return Ok(Some(self.parse_uci_move(&uci_move)?));
-        }
-        
-        Ok(None)
+
+    pub fn from_config(config: &EngineConfig) -> Result<Self> {
+        let mut engine = Self::new(&config.command)?;
+        if let Some(ref name) = config.name {
+            engine.name = name.clone();
+        }
+        for (option, value) in &config.options {
+            engine.set_option(option, value)?;
+        }
+        Ok(engine)
     }
-    
+
+    /// The name reported in the `id name` handshake, unless overridden by config.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the engine missed a deadline; it may still be busy and should be restarted.
+    pub fn is_hung(&self) -> bool {
+        self.hung
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<()> {
+        self.send_command(&format!("setoption name {} value {}", name, value))
+    }
+
+    pub fn new_game(&mut self) -> Result<()> {
+        self.send_command("ucinewgame")?;
+        self.send_command("isready")?;
+        self.wait_for_response("readyok")
+    }
+
+    /// Searches the position for a fixed time; scores are from the side to move.
+    pub fn analyse(&mut self, board: &Board, time_limit: Duration) -> Result<SearchResult> {
+        self.set_position(&board.to_fen(), &[])?;
+        self.go(&SearchLimit::MoveTime(time_limit))
+    }
+
+    /// Like `analyse`, but `None` if the engine has not answered shortly after `time_limit`.
+    pub fn analyse_within(&mut self, board: &Board, time_limit: Duration) -> Result<Option<SearchResult>> {
+        self.set_position(&board.to_fen(), &[])?;
+        let result = self.go_within(&SearchLimit::MoveTime(time_limit), time_limit + ANSWER_GRACE)?;
+        if result.is_none() {
+            self.send_command("stop")?;
+        }
+        Ok(result)
+    }
+
+    pub fn set_position(&mut self, fen: &str, moves: &[Move]) -> Result<()> {
+        let mut command = format!("position fen {}", fen);
+        if !moves.is_empty() {
+            command.push_str(" moves");
+            for chess_move in moves {
+                command.push(' ');
+                command.push_str(&chess_move.to_uci());
+            }
+        }
+        self.send_command(&command)
+    }
+
+    pub fn go(&mut self, limit: &SearchLimit) -> Result<SearchResult> {
+        self.send_go(limit)?;
+        self.read_search_result(None)?
+            .ok_or_else(|| anyhow!("Engine '{}' did not finish its search", self.name))
+    }
+
+    /// Like `go`, but gives up after `timeout`; `None` means the engine did not answer in time.
+    pub fn go_within(&mut self, limit: &SearchLimit, timeout: Duration) -> Result<Option<SearchResult>> {
+        self.send_go(limit)?;
+        self.read_search_result(Some(Instant::now() + timeout))
+    }
+
+    fn send_go(&mut self, limit: &SearchLimit) -> Result<()> {
+        self.discard_output();
+        let command = match limit {
+            SearchLimit::MoveTime(time) => format!("go movetime {}", time.as_millis()),
+            SearchLimit::Clock { white_time, black_time, white_increment, black_increment, moves_to_go } => {
+                let mut command = format!(
+                    "go wtime {} btime {} winc {} binc {}",
+                    white_time.as_millis(),
+                    black_time.as_millis(),
+                    white_increment.as_millis(),
+                    black_increment.as_millis()
+                );
+                if let Some(moves) = moves_to_go {
+                    command.push_str(&format!(" movestogo {}", moves));
+                }
+                command
+            }
+        };
+        self.send_command(&command)
+    }
+
+    // Drops output left over from a search that was given up on, so it isn't taken for the next one's.
+    fn discard_output(&mut self) {
+        while self.lines.try_recv().is_ok() {}
+    }
+
     fn send_command(&mut self, command: &str) -> Result<()> {
         if let Some(ref mut stdin) = self.process.stdin {
             writeln!(stdin, "{}", command)?;
@@ -51,65 +263,182 @@ impl StockfishEngine {
         }
         Ok(())
     }
-    
+
+    // `None` once the deadline has passed without output.
+    fn read_line(&mut self, deadline: Option<Instant>) -> Result<Option<String>> {
+        let received = match deadline {
+            Some(deadline) => self.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => self.lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(line) => Ok(Some(line)),
+            Err(RecvTimeoutError::Timeout) => {
+                self.hung = true;
+                Ok(None)
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(anyhow!("Engine '{}' exited", self.name)),
+        }
+    }
+
+    fn read_until(&mut self, expected: &str) -> Result<Vec<String>> {
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        let mut lines = Vec::new();
+        while let Some(line) = self.read_line(Some(deadline))? {
+            if line.contains(expected) {
+                return Ok(lines);
+            }
+            lines.push(line);
+        }
+        Err(anyhow!("Engine '{}' did not answer '{}' in time", self.name, expected))
+    }
+
     fn wait_for_response(&mut self, expected: &str) -> Result<()> {
-        if let Some(ref mut stdout) = self.process.stdout {
-            let reader = BufReader::new(stdout);
-            for line in reader.lines() {
-                let line = line?;
-                if line.contains(expected) {
-                    return Ok(());
+        self.read_until(expected).map(|_| ())
+    }
+
+    /// Starts an unlimited search; follow it with `poll_analysis` and end it with `stop_analysis`.
+    pub fn start_analysis(&mut self, board: &Board) -> Result<()> {
+        self.set_position(&board.to_fen(), &[])?;
+        self.send_command("go infinite")?;
+        self.analysing = true;
+        Ok(())
+    }
+
+    /// Waits up to `timeout` for output and returns the info lines received, one per MultiPV line.
+    pub fn poll_analysis(&mut self, timeout: Duration) -> Result<Vec<SearchInfo>> {
+        // Some engines end an infinite search on their own, e.g. when mated.
+        Ok(self
+            .poll_search(timeout)?
+            .into_iter()
+            .filter_map(|update| match update {
+                SearchUpdate::Info(info) => Some(info),
+                SearchUpdate::BestMove(_) => None,
+            })
+            .collect())
+    }
+
+    /// Starts a timed search without waiting for it; follow it with `poll_search`.
+    pub fn start_search(&mut self, board: &Board, time_limit: Duration) -> Result<()> {
+        self.set_position(&board.to_fen(), &[])?;
+        self.discard_output();
+        self.send_command(&format!("go movetime {}", time_limit.as_millis()))?;
+        self.analysing = true;
+        Ok(())
+    }
+
+    /// Waits up to `timeout` for output of a background search; the best move ends it.
+    pub fn poll_search(&mut self, timeout: Duration) -> Result<Vec<SearchUpdate>> {
+        let mut updates = Vec::new();
+        let mut wait = timeout;
+        loop {
+            match self.lines.recv_timeout(wait) {
+                Ok(line) => {
+                    if line.starts_with("bestmove") {
+                        self.analysing = false;
+                        let best_move = match line.split_whitespace().nth(1) {
+                            Some(uci_move) if uci_move != "(none)" => Some(Move::from_uci(uci_move)?),
+                            _ => None,
+                        };
+                        updates.push(SearchUpdate::BestMove(best_move));
+                        return Ok(updates);
+                    } else if line.starts_with("info") {
+                        let mut info = SearchInfo::default();
+                        parse_info_line(&line, &mut info);
+                        if info.score.is_some() {
+                            updates.push(SearchUpdate::Info(info));
+                        }
+                    }
+                    wait = Duration::ZERO;
+                }
+                Err(RecvTimeoutError::Timeout) => return Ok(updates),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(anyhow!("Engine '{}' exited during search", self.name))
                 }
             }
         }
-        Err(anyhow!("Expected response '{}' not received", expected))
-    }
-    
-    fn read_best_move(&mut self) -> Result<Option<String>> {
-        if let Some(ref mut stdout) = self.process.stdout {
-            let reader = BufReader::new(stdout);
-            for line in reader.lines() {
-                let line = line?;
-                if line.starts_with("bestmove") {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 2 && parts[1] != "(none)" {
-                        return Ok(Some(parts[1].to_string()));
-                    }
-                    return Ok(None);
+    }
+
+    /// Stops the running search and discards its remaining output.
+    pub fn stop_analysis(&mut self) -> Result<()> {
+        if !self.analysing {
+            return Ok(());
+        }
+        self.analysing = false;
+        self.send_command("stop")?;
+        self.read_search_result(None).map(|_| ())
+    }
+
+    // `None` if the deadline passes before the best move arrives.
+    fn read_search_result(&mut self, deadline: Option<Instant>) -> Result<Option<SearchResult>> {
+        let mut info = SearchInfo::default();
+        while let Some(line) = self.read_line(deadline)? {
+            if line.starts_with("info") {
+                // With MultiPV, only the first line describes the move that will be played.
+                let mut update = SearchInfo { multipv: 0, ..info.clone() };
+                parse_info_line(&line, &mut update);
+                if update.multipv <= 1 {
+                    info = update;
                 }
+            } else if line.starts_with("bestmove") {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                let best_move = match parts.get(1) {
+                    Some(&uci_move) if uci_move != "(none)" => Some(Move::from_uci(uci_move)?),
+                    _ => None,
+                };
+                return Ok(Some(SearchResult { best_move, info }));
             }
         }
         Ok(None)
     }
-    
-    fn parse_uci_move(&self, uci_move: &str) -> Result<Move> {
-        if uci_move.len() < 4 {
-            return Err(anyhow!("Invalid UCI move: {}", uci_move));
-        }
-        
-        let from = Position::from_algebraic(&uci_move[0..2])?;
-        let to = Position::from_algebraic(&uci_move[2..4])?;
-        
-        let promotion = if uci_move.len() == 5 {
-            use crate::chess::PieceType;
-            match uci_move.chars().nth(4).unwrap() {
-                'q' => Some(PieceType::Queen),
-                'r' => Some(PieceType::Rook),
-                'b' => Some(PieceType::Bishop),
-                'n' => Some(PieceType::Knight),
-                _ => None,
+}
+
+/// Folds one `info` line into the running search info; lines without a score are ignored.
+fn parse_info_line(line: &str, info: &mut SearchInfo) {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if !tokens.contains(&"score") {
+        return;
+    }
+
+    let mut i = 1;
+    while i < tokens.len() {
+        match tokens[i] {
+            "multipv" => {
+                if let Some(multipv) = tokens.get(i + 1).and_then(|t| t.parse().ok()) {
+                    info.multipv = multipv;
+                }
+                i += 2;
             }
-        } else {
-            None
-        };
-        
-        Ok(Move::new(from, to, promotion))
+            "depth" => {
+                if let Some(depth) = tokens.get(i + 1).and_then(|t| t.parse().ok()) {
+                    info.depth = depth;
+                }
+                i += 2;
+            }
+            "score" => {
+                let value = tokens.get(i + 2).and_then(|t| t.parse().ok());
+                info.score = match (tokens.get(i + 1), value) {
+                    (Some(&"cp"), Some(cp)) => Some(Score::Centipawns(cp)),
+                    (Some(&"mate"), Some(moves)) => Some(Score::Mate(moves)),
+                    _ => info.score,
+                };
+                i += 3;
+            }
+            "pv" => {
+                info.pv = tokens[i + 1..].iter().map(|t| t.to_string()).collect();
+                break;
+            }
+            _ => i += 1,
+        }
     }
 }
 
 impl Drop for StockfishEngine {
     fn drop(&mut self) {
         let _ = self.send_command("quit");
+        // A hung engine won't read the quit command.
+        if self.hung {
+            let _ = self.process.kill();
+        }
         let _ = self.process.wait();
     }
 }
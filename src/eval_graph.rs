@@ -0,0 +1,177 @@
+use crate::chess::Player;
+use crate::pgn::{self, PgnGame};
+use crate::tui::{self, Line};
+use anyhow::Result;
+use crossterm::{
+    queue,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal,
+};
+use std::io::{self, Write};
+
+/// Evaluations are clipped to this many pawns either way.
+const GRAPH_RANGE: f64 = 5.0;
+/// Rows above and below the zero line.
+const HALF_HEIGHT: usize = 4;
+const LABEL_WIDTH: usize = 4;
+/// Rows a graph takes with its marker and one line of turning points.
+pub const HEIGHT: u16 = HALF_HEIGHT as u16 * 2 + 2;
+/// A change of at least this many pawns between moves counts as a turning point.
+const SWING_THRESHOLD: f64 = 1.5;
+const MAX_SWINGS: usize = 3;
+
+/// The White-relative evaluation from a `[%eval 0.35]` or `[%eval #-3]` comment, in pawns.
+pub fn parse_eval(comment: &str) -> Option<f64> {
+    let start = comment.find("[%eval ")? + "[%eval ".len();
+    let value = comment[start..].split(|c: char| c == ']' || c.is_whitespace()).next()?;
+    match value.strip_prefix('#') {
+        Some(mate) => {
+            mate.parse::<i32>().ok()?;
+            Some(if mate.starts_with('-') { -100.0 } else { 100.0 })
+        }
+        None => value.parse().ok(),
+    }
+}
+
+/// Evaluation after each mainline move, if the game has been annotated.
+pub fn game_evals(game: &PgnGame) -> Option<Vec<Option<f64>>> {
+    let evals: Vec<Option<f64>> = game
+        .moves
+        .iter()
+        .map(|m| m.comment.as_deref().and_then(parse_eval))
+        .collect();
+    evals.iter().any(Option::is_some).then_some(evals)
+}
+
+/// Prints the graph below the current output, as wide as the terminal.
+pub fn draw(game: &PgnGame, evals: &[Option<f64>], current: Option<usize>) -> Result<()> {
+    let width = terminal::size().map_or(80, |(columns, _)| columns as usize);
+    let mut stdout = io::stdout();
+    for row in rows(game, evals, current, width) {
+        for (text, color) in row {
+            if let Some(color) = color {
+                queue!(stdout, SetForegroundColor(color))?;
+            }
+            queue!(stdout, Print(text), ResetColor)?;
+        }
+        queue!(stdout, Print("\n"))?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/// The evaluation across the game, White's advantage upwards, as rows of colored text about
+/// `width` columns wide. The column of `current` (a ply) is marked and turning points listed below.
+pub fn rows(game: &PgnGame, evals: &[Option<f64>], current: Option<usize>, width: usize) -> Vec<Vec<Line>> {
+    if evals.is_empty() {
+        return Vec::new();
+    }
+    // Mated positions carry no eval; hold the last known value instead.
+    let mut last = 0.0;
+    let values: Vec<f64> = evals
+        .iter()
+        .map(|eval| {
+            last = eval.unwrap_or(last);
+            last.clamp(-GRAPH_RANGE, GRAPH_RANGE)
+        })
+        .collect();
+
+    let available = width.saturating_sub(LABEL_WIDTH + 1);
+    let columns = values.len().min(available.max(10));
+    // Each column shows the last ply of its share of the game.
+    let column_ply = |column: usize| ((column + 1) * values.len()).div_ceil(columns) - 1;
+    let current_column = current.map(|ply| ply * columns / values.len());
+
+    let units_per_pawn = (HALF_HEIGHT * 2) as f64 / GRAPH_RANGE;
+    let mut rows = Vec::new();
+    for row in 0..HALF_HEIGHT * 2 {
+        let label = match row {
+            0 => format!("+{:.0}", GRAPH_RANGE),
+            r if r == HALF_HEIGHT => " 0".to_string(),
+            r if r == HALF_HEIGHT * 2 - 1 => format!("-{:.0}", GRAPH_RANGE),
+            _ => String::new(),
+        };
+        let mut spans: Vec<Line> = vec![(format!("{:>width$}│", label, width = LABEL_WIDTH), None)];
+
+        for column in 0..columns {
+            let value = values[column_ply(column)];
+            let units = (value.abs() * units_per_pawn).round() as usize;
+            let cell = if row < HALF_HEIGHT {
+                let level = HALF_HEIGHT - 1 - row;
+                bar_cell(value > 0.0, units, level, '▄')
+            } else {
+                let level = row - HALF_HEIGHT;
+                bar_cell(value < 0.0, units, level, '▀')
+            };
+
+            let color = if Some(column) == current_column {
+                Color::Yellow
+            } else if value >= 0.0 {
+                Color::White
+            } else {
+                Color::DarkGrey
+            };
+            match spans.last_mut() {
+                Some((text, Some(last))) if *last == color => text.push(cell),
+                _ => spans.push((cell.to_string(), Some(color))),
+            }
+        }
+        rows.push(spans);
+    }
+
+    if let Some(column) = current_column {
+        rows.push(vec![(format!("{:>width$} {}▲", "", " ".repeat(column), width = LABEL_WIDTH), None)]);
+    }
+
+    let swings = turning_points(&values);
+    if !swings.is_empty() {
+        let labels: Vec<String> = swings
+            .iter()
+            .map(|&ply| {
+                let before = if ply == 0 { 0.0 } else { values[ply - 1] };
+                format!("{} ({:+.1} → {:+.1})", move_label(game, ply), before, values[ply])
+            })
+            .collect();
+        let text = format!("Turning points: {}", labels.join(", "));
+        rows.extend(tui::wrap(&text, width.max(20)).into_iter().map(|line| vec![(line, None)]));
+    }
+    rows
+}
+
+// One cell of a bar `units` half-cells long, `level` cells away from the zero line.
+fn bar_cell(on_this_side: bool, units: usize, level: usize, half: char) -> char {
+    if !on_this_side || units <= level * 2 {
+        ' '
+    } else if units == level * 2 + 1 {
+        half
+    } else {
+        '█'
+    }
+}
+
+/// The plies with the largest evaluation swings, in game order.
+fn turning_points(values: &[f64]) -> Vec<usize> {
+    let mut swings: Vec<(usize, f64)> = values
+        .iter()
+        .enumerate()
+        .map(|(ply, value)| (ply, (value - if ply == 0 { 0.0 } else { values[ply - 1] }).abs()))
+        .filter(|&(_, swing)| swing >= SWING_THRESHOLD)
+        .collect();
+    swings.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut plies: Vec<usize> = swings.into_iter().take(MAX_SWINGS).map(|(ply, _)| ply).collect();
+    plies.sort_unstable();
+    plies
+}
+
+fn move_label(game: &PgnGame, ply: usize) -> String {
+    let (first_move, white_first) = match game.starting_board() {
+        Ok(board) => (board.move_count() as usize, board.current_player() == Player::White),
+        Err(_) => (1, true),
+    };
+    let offset = ply + usize::from(!white_first);
+    let number = first_move + offset / 2;
+    let dots = if offset.is_multiple_of(2) { "." } else { "..." };
+    let pgn_move = &game.moves[ply];
+    let glyphs: String = pgn_move.nags.iter().filter_map(|&nag| pgn::nag_glyph(nag)).collect();
+    format!("{}{} {}{}", number, dots, pgn_move.san, glyphs)
+}
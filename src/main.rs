@@ -1,27 +1,52 @@
 use anyhow::{anyhow, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use crossterm::{
-    cursor, execute,
-    style::{Color, ResetColor, SetForegroundColor},
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
+    execute, queue,
+    style::Color,
     terminal::{self, ClearType},
 };
+use std::fs::{self, OpenOptions};
 use std::io::{self, Write};
-use std::process::{Command, Stdio};
-use std::thread;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+mod analysis;
+mod annotate;
+mod book;
 mod chess;
+mod eco;
 mod engine;
+mod eval_graph;
+mod match_runner;
+mod pgn;
+mod session;
+mod sprt;
+mod syzygy;
+mod theme;
+mod tournament;
+mod tui;
 mod ui;
+mod viewer;
 
-use chess::{Board, GameState, Move, PieceType, Player, Position};
-use engine::StockfishEngine;
-use ui::ChessUI;
+use book::{BookSelection, OpeningBook};
+use chess::{Board, GameResult, GameState, Move, Player, Position, STARTING_FEN};
+use engine::{Score, SearchInfo, SearchUpdate, StockfishEngine};
+use match_runner::GameOutcome;
+use pgn::{PgnGame, PgnMove, Termination};
+use session::{MoveNote, SavedGame, Takeback};
+use syzygy::Tablebase;
+use tui::{BoardCursor, CommandLine, CursorAction, Dirty, Layout, Rect};
+use ui::{BoardMarks, BoardSize, ChessUI};
 
 #[derive(Parser)]
 #[command(name = "chess-cli")]
 #[command(about = "A CLI chess game with Stockfish integration")]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+    
     #[arg(short, long, default_value = "stockfish")]
     engine_path: String,
     
@@ -30,190 +55,1089 @@ struct Args {
     
     #[arg(short, long)]
     player_white: bool,
+    
+    /// Polyglot opening book (.bin) for the engine's opening moves
+    #[arg(long)]
+    book: Option<PathBuf>,
+    
+    #[arg(long, value_enum, default_value = "weighted")]
+    book_selection: BookSelection,
+    
+    /// Leave the book after this many moves
+    #[arg(long, default_value = "12")]
+    book_depth: u32,
+    
+    /// Syzygy tablebase directories (':' separated, ';' on Windows)
+    #[arg(long)]
+    syzygy_path: Option<String>,
+    
+    /// Append every game to this PGN file
+    #[arg(long, conflicts_with = "pgn_dir")]
+    pgn_out: Option<PathBuf>,
+    
+    /// Directory for per-game PGN files [default: <data dir>/chess-cli/games]
+    #[arg(long)]
+    pgn_dir: Option<PathBuf>,
+    
+    /// Engine thinking time for hints (milliseconds)
+    #[arg(long, default_value = "300")]
+    hint_time: u64,
+    
+    /// Annotate the finished game with the engine before saving it
+    #[arg(long)]
+    annotate: bool,
+    
+    /// Engine thinking time per position when annotating (milliseconds)
+    #[arg(long, default_value = "500")]
+    annotate_time: u64,
+    
+    /// Continue a saved game [default: the game auto-saved on exit]
+    #[arg(long, value_name = "FILE")]
+    resume: Option<Option<PathBuf>>,
+    
+    #[command(flatten)]
+    theme: theme::ThemeArgs,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Play a series of games between two UCI engines
+    Match(match_runner::MatchArgs),
+    /// Run a round-robin or gauntlet tournament between UCI engines
+    Tournament(tournament::TournamentArgs),
+    /// Mark inaccuracies, mistakes and blunders in PGN games with the engine
+    Annotate(annotate::AnnotateArgs),
+    /// Analyse a position with the engine, exploring moves on the board
+    Analyze(analysis::AnalyzeArgs),
+    /// Replay the games in a PGN file
+    View(viewer::ViewArgs),
 }
 
 fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
     
-    execute!(io::stdout(), terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+    if let Some(command) = args.command.take() {
+        return match command {
+            Commands::Match(match_args) => match_runner::run(match_args),
+            Commands::Tournament(tournament_args) => tournament::run(tournament_args),
+            Commands::Analyze(analyze_args) => analysis::run(analyze_args),
+            Commands::Annotate(annotate_args) => annotate::run(annotate_args),
+            Commands::View(view_args) => viewer::run(view_args),
+        };
+    }
     
-    let result = run_game(args);
+    run_game(args)
+}
     
-    execute!(io::stdout(), cursor::MoveTo(0, 0), terminal::Clear(ClearType::All))?;
+/// The engine accepts a draw offer unless it is ahead by more than this many centipawns.
+const DRAW_ACCEPT_SCORE: i32 = 20;
     
-    result
+/// A game against the engine, together with the engine, book and tablebases it uses.
+struct Session {
+    saved: SavedGame,
+    engine: StockfishEngine,
+    book: Option<OpeningBook>,
+    tablebase: Option<Tablebase>,
+    board: Board,
+    /// The last hint, shown in stages while the position stays the same.
+    hint: Option<(String, Move)>,
+    /// Thinking time spent on the coming move so far, in milliseconds.
+    move_ms: u64,
+}
+
+impl Session {
+    fn start(saved: SavedGame) -> Result<Self> {
+        let board = saved.replay()?;
+        let mut engine = StockfishEngine::new(&saved.engine_path)?;
+        let tablebase = match saved.syzygy_path {
+            Some(ref path) => {
+                engine.set_option("SyzygyPath", path)?;
+                Some(Tablebase::open(path)?)
+            }
+            None => None,
+        };
+        let book = match saved.book {
+            Some(ref path) => Some(OpeningBook::open(path, saved.book_selection, saved.book_depth)?),
+            None => None,
+        };
+        Ok(Session { saved, engine, book, tablebase, board, hint: None, move_ms: 0 })
+    }
+    
+    /// Plays a move, noting its thinking time and `eval`, the engine's score for the side making it.
+    fn play(&mut self, chess_move: Move, eval: Option<Score>) -> Result<()> {
+        let mover = self.board.current_player();
+        self.board.make_move(&chess_move)?;
+        self.saved.moves.push(chess_move.to_uci());
+        
+        let ply = self.saved.moves.len();
+        let eval = eval.map(|score| if mover == Player::White { score } else { score.negate() });
+        self.saved.notes.resize(ply - 1, MoveNote::default());
+        self.saved.notes.push(MoveNote { time_ms: Some(std::mem::take(&mut self.move_ms)), eval });
+        Ok(())
+    }
+    
+    /// Takes back the player's last move and the engine's reply.
+    fn undo(&mut self) -> Result<Option<String>> {
+        if self.board.move_history().len() < 2 {
+            return Ok(None);
+        }
+        let engine_move = self.board.undo_move().unwrap();
+        let player_move = self.board.undo_move().unwrap();
+        
+        let mut board = self.board.clone();
+        let mut taken_back = Vec::new();
+        for chess_move in [player_move, engine_move] {
+            taken_back.push(PgnMove { san: board.to_san(&chess_move), ..PgnMove::default() });
+            board.make_move(&chess_move)?;
+        }
+        let moves = pgn::movetext(
+            &taken_back,
+            self.board.move_count(),
+            self.board.current_player() == Player::White,
+        );
+        
+        let ply = self.board.move_history().len();
+        self.saved.moves.truncate(ply);
+        self.saved.takebacks.push(Takeback { ply, moves: moves.clone() });
+        self.engine.new_game()?;
+        Ok(Some(moves))
+    }
+    
+    /// Replays a taken-back move pair; returns false if there is nothing to redo.
+    fn redo(&mut self) -> Result<bool> {
+        let mut redone = false;
+        for _ in 0..2 {
+            if let Some(chess_move) = self.board.redo_move() {
+                self.saved.moves.push(chess_move.to_uci());
+                redone = true;
+            }
+        }
+        if redone {
+            self.engine.new_game()?;
+        }
+        Ok(redone)
+    }
+    
+    /// First names the piece to move, then on a second request the full move, which is also returned.
+    fn hint(&mut self) -> Result<(String, Option<Move>)> {
+        let fen = self.board.to_fen();
+        if let Some((ref hint_fen, ref chess_move)) = self.hint {
+            if *hint_fen == fen {
+                return Ok((format!("play {}", self.board.to_san(chess_move)), Some(chess_move.clone())));
+            }
+        }
+        
+        let time_limit = Duration::from_millis(self.saved.hint_time);
+        let chess_move = self
+            .engine
+            .analyse_within(&self.board, time_limit)?
+            .ok_or_else(|| anyhow!("the engine did not answer"))?
+            .best_move
+            .ok_or_else(|| anyhow!("the engine found no move"))?;
+        let piece = self
+            .board
+            .piece_at(chess_move.from())
+            .ok_or_else(|| anyhow!("the engine suggested an empty square"))?;
+        self.saved.hints += 1;
+        self.hint = Some((fen, chess_move.clone()));
+        let text = format!(
+            "move your {} on {} ('hint' again for the full move)",
+            format!("{:?}", piece.piece_type).to_lowercase(),
+            chess_move.from().to_algebraic()
+        );
+        Ok((text, None))
+    }
+    
+    fn add_thinking_time(&mut self, player: Player, elapsed: Duration) {
+        self.saved.clocks_ms[player as usize] += elapsed.as_millis() as u64;
+        self.move_ms += elapsed.as_millis() as u64;
+    }
+    
+    /// The side the human plays.
+    fn player(&self) -> Player {
+        if self.saved.player_white { Player::White } else { Player::Black }
+    }
+    
+    fn is_player_turn(&self) -> bool {
+        self.board.current_player() == self.player()
+    }
+    
+    fn san_history(&self) -> Result<Vec<String>> {
+        let mut board = Board::from_fen(&self.saved.start_fen)?;
+        let mut sans = Vec::new();
+        for chess_move in self.board.move_history() {
+            sans.push(board.to_san(&chess_move));
+            board.make_move(&chess_move)?;
+        }
+        Ok(sans)
+    }
+    
+    /// The engine's evaluation after the most recent move it scored, from White's side.
+    fn last_eval(&self) -> Option<Score> {
+        self.saved.notes.iter().take(self.saved.moves.len()).rev().find_map(|note| note.eval)
+    }
+    
+    /// The moves so far with their notes, for the move list.
+    fn move_list(&self) -> Result<tui::MoveList> {
+        let start = Board::from_fen(&self.saved.start_fen)?;
+        let entries = self
+            .san_history()?
+            .into_iter()
+            .enumerate()
+            .map(|(ply, san)| {
+                let note = self.saved.notes.get(ply).cloned().unwrap_or_default();
+                tui::MoveEntry {
+                    san,
+                    time: note.time_ms.map(Duration::from_millis),
+                    eval: note.eval.map(|score| analysis::format_score(score, Player::White)),
+                }
+            })
+            .collect();
+        Ok(tui::MoveList { entries, first_number: start.move_count(), black_first: start.current_player() == Player::Black })
+    }
+    
+    fn to_pgn(&self) -> Result<PgnGame> {
+        let mut pgn = PgnGame::new();
+        pgn.set_tag("Event", "Casual game");
+        pgn.set_tag("Site", "chess-cli");
+        pgn.set_tag("Round", "-");
+        pgn.set_tag("White", if self.saved.player_white { "Player" } else { self.engine.name() });
+        pgn.set_tag("Black", if self.saved.player_white { self.engine.name() } else { "Player" });
+        pgn.set_tag("TimeControl", "-");
+        pgn.set_starting_fen(&self.saved.start_fen);
+        if self.saved.hints > 0 {
+            pgn.set_tag("Hints", &self.saved.hints.to_string());
+        }
+        
+        for san in self.san_history()? {
+            pgn.push_move(san, None);
+        }
+        
+        for takeback in &self.saved.takebacks {
+            let text = format!("Takeback: {}", takeback.moves);
+            let slot = match takeback.ply.min(pgn.moves.len()) {
+                0 => match pgn.moves.first_mut() {
+                    Some(first) => &mut first.starting_comment,
+                    None => &mut pgn.result_comment,
+                },
+                ply => &mut pgn.moves[ply - 1].comment,
+            };
+            match slot {
+                Some(comment) => comment.push_str(&format!("; {}", text)),
+                None => *slot = Some(text),
+            }
+        }
+        eco::tag_game(&mut pgn);
+        Ok(pgn)
+    }
 }
 
 fn run_game(args: Args) -> Result<()> {
-    let mut board = Board::new();
-    let mut engine = StockfishEngine::new(&args.engine_path)?;
-    let ui = ChessUI::new();
-    
-    let player_is_white = args.player_white;
-    let time_limit = Duration::from_millis(args.time_limit);
-    
-    println!("🏰 Chess CLI - Playing against Stockfish");
-    println!("Player: {} | Engine: {}", 
-        if player_is_white { "White" } else { "Black" },
-        if player_is_white { "Black" } else { "White" }
-    );
-    println!("Enter moves in algebraic notation (e.g., e4, Nf3, O-O)");
-    println!("Type 'quit' to exit, 'help' for commands\n");
-    
-    loop {
-        ui.display_board(&board)?;
-        ui.display_game_info(&board)?;
-        
-        match board.game_state() {
-            GameState::Checkmate => {
-                let winner = if board.current_player() == Player::White { "Black" } else { "White" };
-                println!("🏁 Checkmate! {} wins!", winner);
-                break;
-            }
-            GameState::Stalemate => {
-                println!("🤝 Stalemate! Game is a draw.");
-                break;
-            }
-            GameState::Draw => {
-                println!("🤝 Draw!");
-                break;
-            }
-            GameState::InProgress => {}
-        }
-        
-        let player_turn = (board.current_player() == Player::White) == player_is_white;
-        
-        if player_turn {
-            match get_player_move(&mut board)? {
-                Some(chess_move) => {
-                    if board.is_legal_move(&chess_move) {
-                        board.make_move(&chess_move)?;
-                        println!("✓ Move played: {}", chess_move.to_algebraic());
-                    } else {
-                        println!("❌ Illegal move! Try again.");
-                        continue;
+    let appearance = args.theme.appearance()?;
+    let saved = match args.resume {
+        Some(ref path) => {
+            let path = match path {
+                Some(path) => path.clone(),
+                None => session::autosave_path()?,
+            };
+            SavedGame::load(&path)?
+        }
+        None => SavedGame {
+            start_fen: STARTING_FEN.to_string(),
+            moves: Vec::new(),
+            player_white: args.player_white,
+            clocks_ms: [0, 0],
+            engine_path: args.engine_path.clone(),
+            time_limit: args.time_limit,
+            book: args.book.clone(),
+            book_selection: args.book_selection,
+            book_depth: args.book_depth,
+            syzygy_path: args.syzygy_path.clone(),
+            hint_time: args.hint_time,
+            hints: 0,
+            takebacks: Vec::new(),
+            notes: Vec::new(),
+        },
+    };
+    let mut session = Session::start(saved)?;
+    let mut ui = ChessUI::new(appearance);
+    ui.set_orientation(session.player());
+    
+    let terminal = tui::Terminal::enter()?;
+    ui.fit_terminal(tui::SPARE);
+    let mut view = GameView::new(&session, ui.size());
+    view.message("🏰 Playing Stockfish: enter moves like e4, Nf3 or e2e4; 'help' lists commands");
+    
+    let mut turn_started = Instant::now();
+    let mut searching = false;
+    let mut search_score = None;
+    let mut position_changed = true;
+    
+    let (outcome, farewell) = 'game: loop {
+        if position_changed {
+            position_changed = false;
+            view.dirty.merge(Dirty::position());
+            view.best_move = None;
+            view.moves_scroll = 0;
+            view.set_eval(session.last_eval().map(|score| (score, Player::White)));
+            turn_started = Instant::now();
+        
+            if let Some((outcome, message)) = game_over(&session) {
+                break (outcome, message);
+            }
+            
+            if !session.is_player_turn() {
+                if let Some(chess_move) = session.book.as_ref().and_then(|book| book.pick_move(&session.board)) {
+                    view.message(format!("📖 Stockfish plays from book: {}", session.board.to_san(&chess_move)));
+                    session.play(chess_move, None)?;
+                    autosave(&session, &mut view);
+                    position_changed = true;
+                    continue;
+                }
+                
+                let time_limit = Duration::from_millis(session.saved.time_limit);
+                session.engine.start_search(&session.board, time_limit)?;
+                searching = true;
+                search_score = None;
+                view.set_engine_lines(vec![("🤖 Stockfish is thinking...".to_string(), None)]);
+            }
+        }
+        
+        if view.clock_seconds != turn_started.elapsed().as_secs() {
+            view.clock_seconds = turn_started.elapsed().as_secs();
+            view.dirty.clocks = true;
+        }
+        view.draw(&ui, &session, turn_started)?;
+        
+        if searching {
+            for update in session.engine.poll_search(Duration::ZERO)? {
+                match update {
+                    SearchUpdate::Info(info) => {
+                        search_score = info.score.or(search_score);
+                        view.show_search(&session, &info);
+                    }
+                    SearchUpdate::BestMove(best_move) => {
+                        searching = false;
+                        session.add_thinking_time(session.board.current_player(), turn_started.elapsed());
+                        match best_move {
+                            Some(chess_move) => {
+                                view.message(format!("🤖 Stockfish plays: {}", session.board.to_san(&chess_move)));
+                                session.play(chess_move, search_score)?;
+                                autosave(&session, &mut view);
+                                position_changed = true;
+                            }
+                            None => {
+                                break 'game (
+                                    unterminated("Engine failed to return a move"),
+                                    "🤖 Stockfish couldn't find a move!".to_string(),
+                                );
+                            }
+                        }
                     }
                 }
-                None => break,
             }
-        } else {
-            println!("🤖 Stockfish is thinking...");
+            if position_changed {
+                continue;
+            }
+        }
             
-            let engine_move = engine.get_best_move(&board, time_limit)?;
-            match engine_move {
-                Some(chess_move) => {
-                    board.make_move(&chess_move)?;
-                    println!("🤖 Stockfish plays: {}", chess_move.to_algebraic());
-                }
-                None => {
-                    println!("🤖 Stockfish couldn't find a move!");
+        if !event::poll(Duration::from_millis(50))? {
+            continue;
+        }
+        let input = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                if view.show_help {
+                    view.show_help = false;
+                    view.redraw()?;
+                    continue;
+                }
+                match view.handle_key(key, &session.board, ui.orientation()) {
+                    Some(input) => input,
+                    None => continue,
+                }
+            }
+            Event::Mouse(mouse) if !view.show_help => match view.handle_mouse(mouse, &ui, &session.board) {
+                Some(input) => input,
+                None => continue,
+            },
+            Event::Resize(columns, rows) => {
+                ui.fit(columns, rows, tui::SPARE);
+                view.resize(columns, rows, ui.size())?;
+                continue;
+            }
+            _ => continue,
+        };
+        if searching
+            && !matches!(input, PlayerInput::Quit
+                    | PlayerInput::Help
+                    | PlayerInput::Redraw
+                    | PlayerInput::Flip
+                    | PlayerInput::EvalBar
+                    | PlayerInput::Save(_))
+        {
+            view.error("⏳ Stockfish is thinking; wait for its move");
+            continue;
+        }
+        if session.is_player_turn() {
+            session.add_thinking_time(session.board.current_player(), turn_started.elapsed());
+            turn_started = Instant::now();
+        }
+        
+        match input {
+            PlayerInput::Move(chess_move) => {
+                if session.board.is_legal_move(&chess_move) {
+                    view.message(format!("✓ Move played: {}", session.board.to_san(&chess_move)));
+                    session.play(chess_move, None)?;
+                    autosave(&session, &mut view);
+                    position_changed = true;
+                } else {
+                    view.error("❌ Illegal move! Try again.");
+                }
+            }
+            PlayerInput::Save(path) => {
+                match path.map_or_else(session::autosave_path, Ok).and_then(|path| {
+                    session.saved.save(&path)?;
+                    Ok(path)
+                }) {
+                    Ok(path) => view.message(format!("💾 Game saved to {}", path.display())),
+                    Err(e) => view.error(format!("❌ Could not save game: {}", e)),
+                }
+            }
+            PlayerInput::Load(path) => {
+                match path
+                    .map_or_else(session::autosave_path, Ok)
+                    .and_then(|path| SavedGame::load(&path))
+                    .and_then(Session::start)
+                {
+                    Ok(loaded) => {
+                        session = loaded;
+                        ui.set_orientation(session.player());
+                        view.message("📂 Game loaded");
+                        view.set_engine_lines(session_info(&session));
+                        position_changed = true;
+                    }
+                    Err(e) => view.error(format!("❌ Could not load game: {}", e)),
+                }
+            }
+            PlayerInput::Undo => {
+                match session.undo()? {
+                    Some(moves) => {
+                        view.message(format!("↩️  Took back {}", moves));
+                        position_changed = true;
+                    }
+                    None => view.error("❌ Nothing to take back"),
+                }
+                autosave(&session, &mut view);
+            }
+            PlayerInput::Redo => {
+                if session.redo()? {
+                    view.message("↪️  Moves replayed");
+                    autosave(&session, &mut view);
+                    position_changed = true;
+                } else {
+                    view.error("❌ Nothing to redo");
+                }
+            }
+            PlayerInput::Hint => match session.hint() {
+                Ok((hint, chess_move)) => {
+                    view.message(format!("💡 Hint: {}", hint));
+                    if chess_move.is_some() {
+                        view.set_best_move(chess_move);
+                    }
+                }
+                Err(e) => view.error(format!("❌ No hint available: {}", e)),
+            },
+            PlayerInput::Resign => {
+                let side = session.board.current_player();
+                break (
+                    GameOutcome::new(GameResult::win_for(side.opposite()), format!("{:?} resigns", side), Termination::Normal),
+                    "🏳️  You resign. Stockfish wins!".to_string(),
+                );
+            }
+            PlayerInput::OfferDraw => {
+                view.message("🤝 You offer a draw...");
+                view.draw(&ui, &session, turn_started)?;
+                let time_limit = Duration::from_millis(session.saved.time_limit);
+                let Some(result) = session.engine.analyse_within(&session.board, time_limit)? else {
+                    view.error("❌ Stockfish did not answer; the draw offer is declined.");
+                    continue;
+                };
+                // The score is from the player's side, who is to move.
+                if result.info.score.map_or(0, |score| -score.as_centipawns()) <= DRAW_ACCEPT_SCORE {
+                    break (
+                        GameOutcome::new(GameResult::Draw, "Draw by agreement".to_string(), Termination::Normal),
+                        "🤝 Stockfish accepts. Game drawn by agreement.".to_string(),
+                    );
+                }
+                view.message("🤖 Stockfish declines the draw offer.");
+            }
+            PlayerInput::ClaimDraw => match claimable_draw(&session.board) {
+                Some(reason) => {
+                    break (
+                        GameOutcome::new(GameResult::Draw, reason.to_string(), Termination::Normal),
+                        format!("🤝 Draw claimed! {}.", reason),
+                    );
+                }
+                None => view.error(format!(
+                    "❌ No draw to claim (position seen {}x, {} moves without a capture or pawn move)",
+                    session.board.repetition_count(),
+                    session.board.halfmove_clock() / 2
+                )),
+            },
+            PlayerInput::Help => {
+                view.show_help = true;
+                tui::draw_overlay(HELP)?;
+            }
+            PlayerInput::Flip => {
+                ui.flip();
+                view.message(format!("🔄 Board flipped: {:?} at the bottom", ui.orientation()));
+                view.dirty.board = true;
+            }
+            PlayerInput::EvalBar => {
+                ui.toggle_eval_bar();
+                view.redraw()?;
+                view.message(if ui.show_eval_bar() { "📊 Evaluation bar shown" } else { "📊 Evaluation bar hidden" });
+            }
+            PlayerInput::Redraw => view.redraw()?,
+            PlayerInput::Quit => {
+                if searching {
+                    session.engine.stop_analysis()?;
+                }
+                break (unterminated("Game abandoned by the player"), "👋 Game abandoned.".to_string());
+            }
+        }
+    };
+    
+    // Leave the final position on screen until the player has seen it.
+    if outcome.termination != Termination::Unterminated {
+        view.message(format!("{} Press any key to exit.", farewell));
+        view.dirty.merge(Dirty::position());
+        view.draw(&ui, &session, turn_started)?;
+        loop {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
                     break;
                 }
             }
         }
+    }
+    drop(terminal);
+    println!("{}", farewell);
         
-        thread::sleep(Duration::from_millis(500));
+    // Only unfinished games are kept for --resume.
+    if outcome.termination == Termination::Unterminated {
+        if let Err(e) = session::autosave_path().and_then(|path| session.saved.save(&path)) {
+            println!("❌ Auto-save failed: {}", e);
+        }
+        println!("💾 Continue this game later with --resume");
+    } else if let Ok(path) = session::autosave_path() {
+        let _ = fs::remove_file(path);
+    }
+    
+    let mut pgn = session.to_pgn()?;
+    if outcome.termination != Termination::Unterminated {
+        pgn.set_result(outcome.result);
+    }
+    pgn.set_tag("Termination", outcome.termination.to_pgn());
+    pgn.result_comment = Some(outcome.reason);
+    
+    if args.annotate && !pgn.moves.is_empty() {
+        println!("🔍 Annotating the game...");
+        let time_limit = Duration::from_millis(args.annotate_time);
+        match annotate::annotate_game(&mut session.engine, &mut pgn, time_limit) {
+            Ok(report) => report.print(),
+            Err(e) => println!("❌ Could not annotate game: {}", e),
+        }
+    }
+    
+    match save_pgn(&args, &pgn) {
+        Ok(path) => println!("💾 Game saved to {}", path.display()),
+        Err(e) => println!("❌ Could not save game: {}", e),
     }
     
     println!("\nGame Over! Thanks for playing!");
     Ok(())
 }
 
-fn get_player_move(board: &Board) -> Result<Option<Move>> {
-    loop {
-        print!("Enter your move: ");
-        io::stdout().flush()?;
+/// The outcome if the game is over in the current position, and the message announcing it.
+fn game_over(session: &Session) -> Option<(GameOutcome, String)> {
+    let board = &session.board;
+    match board.game_state() {
+        GameState::Checkmate => {
+            let winner = if board.current_player() == Player::White { "Black" } else { "White" };
+            return Some((
+                GameOutcome::new(
+                    GameResult::win_for(board.current_player().opposite()),
+                    format!("{} mates", winner),
+                    Termination::Normal,
+                ),
+                format!("🏁 Checkmate! {} wins!", winner),
+            ));
+        }
+        GameState::Stalemate => {
+            return Some((
+                GameOutcome::new(GameResult::Draw, "Draw by stalemate".to_string(), Termination::Normal),
+                "🤝 Stalemate! Game is a draw.".to_string(),
+            ));
+        }
+        GameState::Draw => {
+            return Some((
+                GameOutcome::new(GameResult::Draw, draw_reason(board).to_string(), Termination::Normal),
+                "🤝 Draw!".to_string(),
+            ));
+        }
+        GameState::InProgress => {}
+    }
         
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let input = input.trim();
+    let outcome = session.tablebase.as_ref().and_then(|tb| match_runner::tablebase_outcome(tb, board))?;
+    let message = match outcome.result {
+        GameResult::Draw => "🤝 Tablebase draw!".to_string(),
+        result => format!("🏁 Tablebase win for {}!", if result == GameResult::WhiteWins { "White" } else { "Black" }),
+    };
+    Some((outcome, message))
+}
         
-        match input.to_lowercase().as_str() {
-            "quit" | "exit" | "q" => return Ok(None),
-            "help" | "h" => {
-                print_help();
-                continue;
+/// The full-screen view of a game: what the status and engine panes say and which panes need redrawing.
+struct GameView {
+    layout: Layout,
+    dirty: Dirty,
+    command: CommandLine,
+    message: tui::Line,
+    engine_lines: Vec<tui::Line>,
+    show_help: bool,
+    /// Seconds on the running clock when it was last drawn.
+    clock_seconds: u64,
+    /// Keys go to the board cursor rather than the command line.
+    board_focus: bool,
+    cursor: BoardCursor,
+    /// The square a piece is being dragged from.
+    drag_from: Option<Position>,
+    /// The engine's current best move or a revealed hint, marked on the board.
+    best_move: Option<Move>,
+    /// The latest evaluation and the side it is from, for the eval bar.
+    eval: Option<(Score, Player)>,
+    /// Rows the move list is scrolled back from the latest move.
+    moves_scroll: usize,
+}
+
+impl GameView {
+    fn new(session: &Session, board_size: BoardSize) -> Self {
+        GameView {
+            layout: Layout::for_terminal(board_size),
+            dirty: Dirty::all(),
+            command: CommandLine::default(),
+            message: (String::new(), None),
+            engine_lines: session_info(session),
+            show_help: false,
+            clock_seconds: 0,
+            board_focus: false,
+            cursor: BoardCursor::new(session.player()),
+            drag_from: None,
+            best_move: None,
+            eval: session.last_eval().map(|score| (score, Player::White)),
+            moves_scroll: 0,
+        }
+    }
+    
+    /// Routes a key to the board cursor or the command line; returns the input once one is complete.
+    fn handle_key(&mut self, key: KeyEvent, board: &Board, orientation: Player) -> Option<PlayerInput> {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Some(PlayerInput::Quit);
+        }
+        if key.code == KeyCode::Tab {
+            self.set_board_focus(!self.board_focus);
+            return None;
+        }
+        if let KeyCode::PageUp | KeyCode::PageDown = key.code {
+            let page = self.layout.moves.height.saturating_sub(2) as isize;
+            self.scroll_moves(if key.code == KeyCode::PageUp { page } else { -page });
+            return None;
+        }
+        
+        if self.board_focus {
+            match self.cursor.handle_key(key, board, orientation) {
+                CursorAction::Play(chess_move) => return Some(PlayerInput::Move(chess_move)),
+                CursorAction::Changed => {
+                    self.cursor_changed();
+                    return None;
+                }
+                CursorAction::Unhandled if key.code == KeyCode::Esc => {
+                    self.set_board_focus(false);
+                    return None;
+                }
+                // Anything else starts a typed command.
+                CursorAction::Unhandled => self.set_board_focus(false),
             }
-            "board" | "b" => continue,
-            _ => {
-                match parse_algebraic_notation(input, board) {
-                    Ok(chess_move) => return Ok(Some(chess_move)),
-                    Err(e) => {
-                        println!("❌ Invalid move '{}': {}", input, e);
-                        continue;
+        }
+        
+        self.dirty.command = true;
+        let line = self.command.handle_key(key)?;
+        match parse_player_input(&line, board) {
+            Ok(input) => Some(input),
+            Err(e) => {
+                self.error(format!("❌ {}", e));
+                None
+            }
+        }
+    }
+    
+    /// Clicking a piece and then a target, or dragging the piece there, plays the move.
+    fn handle_mouse(&mut self, mouse: MouseEvent, ui: &ChessUI, board: &Board) -> Option<PlayerInput> {
+        if self.layout.moves.contains(mouse.column, mouse.row) {
+            match mouse.kind {
+                MouseEventKind::ScrollUp => self.scroll_moves(3),
+                MouseEventKind::ScrollDown => self.scroll_moves(-3),
+                _ => {}
+            }
+        }
+        let origin = (self.layout.board.x, self.layout.board.y);
+        let square = ui.square_at(origin, mouse.column, mouse.row);
+        let action = match (mouse.kind, square) {
+            (MouseEventKind::Down(MouseButton::Left), Some(square)) => {
+                if !self.board_focus {
+                    self.set_board_focus(true);
+                }
+                let action = self.cursor.select(board, square);
+                self.drag_from = self.cursor.selected.filter(|&selected| selected == square);
+                action
+            }
+            (MouseEventKind::Down(MouseButton::Left), None) if self.cursor.promotion.is_some() => {
+                let piece_type = self.cursor.promotion_choice_at(self.layout.status, mouse.column, mouse.row)?;
+                self.cursor.promote(board, piece_type)
+            }
+            (MouseEventKind::Drag(MouseButton::Left), Some(square)) if self.drag_from.is_some() => {
+                self.cursor.square = square;
+                CursorAction::Changed
+            }
+            (MouseEventKind::Up(MouseButton::Left), square) => {
+                let from = self.drag_from.take()?;
+                match square {
+                    Some(square) if square != from && self.cursor.selected == Some(from) => {
+                        self.cursor.select(board, square)
+                    }
+                    _ => {
+                        self.cursor.square = from;
+                        CursorAction::Changed
                     }
                 }
             }
+            _ => return None,
+        };
+        
+        match action {
+            CursorAction::Play(chess_move) => Some(PlayerInput::Move(chess_move)),
+            _ => {
+                self.cursor_changed();
+                None
+            }
         }
     }
-}
-
-fn print_help() {
-    println!("\n📖 Help:");
-    println!("  • Enter moves in algebraic notation:");
-    println!("    - Pawn moves: e4, d5, exd5");
-    println!("    - Piece moves: Nf3, Bb5, Qh4");
-    println!("    - Castling: O-O (kingside), O-O-O (queenside)");
-    println!("    - Promotion: e8=Q");
-    println!("  • Commands:");
-    println!("    - help/h: Show this help");
-    println!("    - quit/q: Quit game");
-    println!("    - board/b: Redraw board\n");
-}
-
-fn parse_algebraic_notation(notation: &str, board: &Board) -> Result<Move> {
-    let notation = notation.trim().to_lowercase();
     
+    /// Scrolls the move list back by `rows`, or towards the latest move when negative.
+    fn scroll_moves(&mut self, rows: isize) {
+        self.moves_scroll = self.moves_scroll.saturating_add_signed(rows);
+        self.dirty.moves = true;
+    }
     
-    if notation == "o-o" || notation == "0-0" {
-        let from = if board.current_player() == Player::White { 
-            Position::from_algebraic("e1")? 
-        } else { 
-            Position::from_algebraic("e8")? 
-        };
-        let to = if board.current_player() == Player::White { 
-            Position::from_algebraic("g1")? 
-        } else { 
-            Position::from_algebraic("g8")? 
-        };
-        return Ok(Move::new(from, to, None));
+    fn cursor_changed(&mut self) {
+        self.message(self.cursor.hint());
+        self.dirty.board = true;
     }
     
-    if notation == "o-o-o" || notation == "0-0-0" {
-        let from = if board.current_player() == Player::White { 
-            Position::from_algebraic("e1")? 
-        } else { 
-            Position::from_algebraic("e8")? 
-        };
-        let to = if board.current_player() == Player::White { 
-            Position::from_algebraic("c1")? 
-        } else { 
-            Position::from_algebraic("c8")? 
-        };
-        return Ok(Move::new(from, to, None));
+    fn set_board_focus(&mut self, board_focus: bool) {
+        self.board_focus = board_focus;
+        self.cursor.selected = None;
+        self.cursor.promotion = None;
+        if board_focus {
+            self.message(self.cursor.hint());
+        } else {
+            self.message("⌨️  Type a move or command; Tab moves pieces with the cursor keys");
+        }
+        self.dirty.board = true;
+        self.dirty.command = true;
+    }
+    
+    fn message(&mut self, text: impl Into<String>) {
+        self.message = (text.into(), None);
+        self.dirty.status = true;
+    }
+    
+    fn error(&mut self, text: impl Into<String>) {
+        self.message = (text.into(), Some(Color::Red));
+        self.dirty.status = true;
+    }
+    
+    fn set_engine_lines(&mut self, lines: Vec<tui::Line>) {
+        self.engine_lines = lines;
+        self.dirty.engine = true;
+    }
+    
+    fn show_search(&mut self, session: &Session, info: &SearchInfo) {
+        let board = &session.board;
+        let score = info.score.map_or("?".to_string(), |score| analysis::format_score(score, board.current_player()));
+        let mut lines = vec![(format!("🤖 {} | depth {} | {}", session.engine.name(), info.depth, score), None)];
+        let line = analysis::pv_movetext(board, &info.pv);
+        lines.extend(tui::wrap(&line, self.layout.engine.width as usize).into_iter().map(|text| (text, Some(Color::DarkGrey))));
+        self.set_engine_lines(lines);
+        if let Some(score) = info.score {
+            self.set_eval(Some((score, board.current_player())));
+        }
+        self.set_best_move(info.pv.first().and_then(|uci| Move::from_uci(uci).ok()));
     }
     
+    fn set_eval(&mut self, eval: Option<(Score, Player)>) {
+        if self.eval != eval {
+            self.eval = eval;
+            self.dirty.eval_bar = true;
+        }
+    }
     
-    if notation.len() == 2 {
-        let to_pos = Position::from_algebraic(&notation)?;
+    fn set_best_move(&mut self, best_move: Option<Move>) {
+        if self.best_move != best_move {
+            self.best_move = best_move;
+            self.dirty.board = true;
+        }
+    }
+    
+    fn resize(&mut self, columns: u16, rows: u16, board_size: BoardSize) -> Result<()> {
+        self.layout = Layout::new(columns, rows, board_size);
+        if self.show_help {
+            return tui::draw_overlay(HELP);
+        }
+        self.redraw()
+    }
+    
+    fn redraw(&mut self) -> Result<()> {
+        execute!(io::stdout(), terminal::Clear(ClearType::All))?;
+        self.dirty = Dirty::all();
+        Ok(())
+    }
+    
+    /// Draws the panes that changed since the last frame.
+    fn draw(&mut self, ui: &ChessUI, session: &Session, turn_started: Instant) -> Result<()> {
+        if self.show_help {
+            return Ok(());
+        }
+        let dirty = std::mem::take(&mut self.dirty);
+        let layout = self.layout;
+        if !layout.fits() {
+            return if dirty.board { tui::draw_too_small(&layout) } else { Ok(()) };
+        }
+        queue!(io::stdout(), cursor::Hide)?;
         
-        // Piyon hamlesi olarak varsay
-        let from_rank = if board.current_player() == Player::White {
-            if to_pos.rank == 3 { 1 } else { to_pos.rank - 1 }
-        } else {
-            if to_pos.rank == 4 { 6 } else { to_pos.rank + 1 }
-        };
+        if dirty.board {
+            let mut marks = if self.board_focus { self.cursor.marks(&session.board) } else { BoardMarks::default() };
+            marks.best_move = self.best_move.clone();
+            ui.draw_board(&session.board, &marks, layout.board.x, layout.board.y)?;
+        }
+        if dirty.board || dirty.eval_bar {
+            let (score, side) = self.eval.map_or((None, Player::White), |(score, side)| (Some(score), side));
+            ui.draw_eval_bar(score, side, layout.board.x, layout.board.y)?;
+        }
+        if dirty.info {
+            tui::draw_lines(layout.info, &ui.game_info(&session.board, session.tablebase.as_ref()))?;
+        }
+        if dirty.clocks {
+            tui::draw_lines(layout.clocks, &clock_lines(session, turn_started))?;
+        }
+        if dirty.moves {
+            let rect = layout.moves;
+            let list = session.move_list()?;
+            let current = list.entries.len().checked_sub(1);
+            self.moves_scroll =
+                list.draw(Rect::new(rect.x, rect.y + 1, rect.width, rect.height - 1), current, self.moves_scroll)?;
+            let title = if self.moves_scroll > 0 { "Moves (scrolled back; PgDn for the latest)" } else { "Moves" };
+            tui::draw_title(rect, title)?;
+        }
+        if dirty.engine {
+            tui::draw_lines(layout.engine, &self.engine_lines)?;
+        }
+        if dirty.status {
+            tui::draw_lines(layout.status, std::slice::from_ref(&self.message))?;
+        }
         
-        let from_pos = Position::new(to_pos.file, from_rank)?;
-        return Ok(Move::new(from_pos, to_pos, None));
+        let prompt = match (self.board_focus, session.is_player_turn()) {
+            (true, _) => "Board (Tab to type)> ",
+            (false, true) => "Your move> ",
+            (false, false) => "> ",
+        };
+        self.command.draw(layout.command, prompt)?;
+        if self.board_focus {
+            queue!(io::stdout(), cursor::Hide)?;
+        }
+        io::stdout().flush()?;
+        Ok(())
     }
+}
+
+/// Time used by each side, counting the running turn for the side to move.
+fn clock_lines(session: &Session, turn_started: Instant) -> Vec<tui::Line> {
+    let side_to_move = session.board.current_player();
+    [Player::White, Player::Black]
+        .into_iter()
+        .map(|player| {
+            let mut millis = session.saved.clocks_ms[player as usize];
+            if player == side_to_move {
+                millis += turn_started.elapsed().as_millis() as u64;
+            }
+            let name = if (player == Player::White) == session.saved.player_white { "You" } else { session.engine.name() };
+            let marker = if player == side_to_move { "▶" } else { " " };
+            let color = (player == side_to_move).then_some(Color::Green);
+            (format!("{} {:<5} {:<20} {:>8}", marker, format!("{:?}", player), name, format_clock(millis)), color)
+        })
+        .collect()
+}
+
+fn session_info(session: &Session) -> Vec<tui::Line> {
+    let saved = &session.saved;
+    let mut lines = vec![(
+        format!(
+            "Player: {} | Engine: {}",
+            if saved.player_white { "White" } else { "Black" },
+            if saved.player_white { "Black" } else { "White" }
+        ),
+        None,
+    )];
+    if let Some(ref book) = session.book {
+        lines.push((format!("📖 Opening book: {} entries", book.entry_count()), None));
+    }
+    if let Some(ref tablebase) = session.tablebase {
+        lines.push((format!("📚 Syzygy tablebases: up to {} pieces", tablebase.max_pieces()), None));
+    }
+    lines
+}
     
+fn format_clock(millis: u64) -> String {
+    let seconds = millis / 1000;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
     
-    if notation.len() == 4 {
-        let from_pos = Position::from_algebraic(&notation[0..2])?;
-        let to_pos = Position::from_algebraic(&notation[2..4])?;
-        return Ok(Move::new(from_pos, to_pos, None));
+fn autosave(session: &Session, view: &mut GameView) {
+    if let Err(e) = session::autosave_path().and_then(|path| session.saved.save(&path)) {
+        view.error(format!("❌ Auto-save failed: {}", e));
+    }
+}
+
+fn unterminated(reason: &str) -> GameOutcome {
+    GameOutcome::new(GameResult::Draw, reason.to_string(), Termination::Unterminated)
+}
+
+/// A draw the player to move may claim: threefold repetition or the fifty-move rule.
+fn claimable_draw(board: &Board) -> Option<&'static str> {
+    if board.repetition_count() >= 3 {
+        Some("Draw by 3-fold repetition")
+    } else if board.halfmove_clock() >= 100 {
+        Some("Draw by fifty moves rule")
+    } else {
+        None
+    }
+}
+
+fn draw_reason(board: &Board) -> &'static str {
+    if board.is_insufficient_material() {
+        "Draw by insufficient mating material"
+    } else if board.repetition_count() >= 5 {
+        "Draw by 5-fold repetition"
+    } else {
+        "Draw by seventy-five moves rule"
+    }
+}
+
+/// Appends the game to `--pgn-out`, or writes it to its own file in the PGN directory.
+fn save_pgn(args: &Args, game: &PgnGame) -> Result<PathBuf> {
+    let path = match args.pgn_out {
+        Some(ref path) => path.clone(),
+        None => {
+            let dir = match args.pgn_dir {
+                Some(ref dir) => dir.clone(),
+                None => session::data_dir()?.join("games"),
+            };
+            fs::create_dir_all(&dir)?;
+            unused_path(&dir, &pgn::timestamp())
+        }
+    };
+    
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    match_runner::write_pgn(&mut file, game)?;
+    Ok(path)
+}
+
+fn unused_path(dir: &Path, stem: &str) -> PathBuf {
+    let mut path = dir.join(format!("{}.pgn", stem));
+    let mut suffix = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.pgn", stem, suffix));
+        suffix += 1;
+    }
+    path
+}
+
+enum PlayerInput {
+    Move(Move),
+    Save(Option<PathBuf>),
+    Load(Option<PathBuf>),
+    Undo,
+    Redo,
+    Hint,
+    Resign,
+    OfferDraw,
+    ClaimDraw,
+    Help,
+    Flip,
+    EvalBar,
+    Redraw,
+    Quit,
+}
+
+fn parse_player_input(input: &str, board: &Board) -> Result<PlayerInput> {
+    let input = input.trim();
+    let (command, argument) = match input.split_once(char::is_whitespace) {
+        Some((command, argument)) => (command, Some(PathBuf::from(argument.trim()))),
+        None => (input, None),
+    };
+    match command.to_lowercase().as_str() {
+        "save" => return Ok(PlayerInput::Save(argument)),
+        "load" => return Ok(PlayerInput::Load(argument)),
+        _ => {}
     }
     
-    Err(anyhow!("Could not parse move: '{}'. Try format like 'e4' or 'e2e4'", notation))
+    match input.to_lowercase().as_str() {
+        "quit" | "exit" | "q" => Ok(PlayerInput::Quit),
+        "undo" | "u" => Ok(PlayerInput::Undo),
+        "redo" => Ok(PlayerInput::Redo),
+        "hint" => Ok(PlayerInput::Hint),
+        "resign" => Ok(PlayerInput::Resign),
+        "draw" => Ok(PlayerInput::OfferDraw),
+        "claim" => Ok(PlayerInput::ClaimDraw),
+        "help" | "h" => Ok(PlayerInput::Help),
+        "flip" | "f" => Ok(PlayerInput::Flip),
+        "eval" => Ok(PlayerInput::EvalBar),
+        "board" | "b" | "" => Ok(PlayerInput::Redraw),
+        _ => board.parse_move(input).map(PlayerInput::Move),
+    }
 }
+    
+const HELP: &[&str] = &[
+    "📖 Help (help/h shows it again):",
+    "  • Enter moves in SAN (e4, exd5, Nf3, O-O, e8=Q) or as squares (e2e4, e7e8q)",
+    "  • Commands:",
+    "    - undo/u: Take back your last move and the engine's reply",
+    "    - redo: Replay moves you took back",
+    "    - hint: Show the piece the engine would move; again for the full move",
+    "    - resign: Resign the game",
+    "    - draw: Offer a draw; the engine accepts if it is not better",
+    "    - claim: Claim a draw by threefold repetition or the fifty-move rule",
+    "    - save [file]: Save the game (default: the auto-save file)",
+    "    - load [file]: Load a saved game",
+    "    - quit/q: Quit game (it is auto-saved for --resume); Ctrl-C works too",
+    "    - flip/f: Turn the board around",
+    "    - eval: Show or hide the evaluation bar beside the board",
+    "    - board/b: Redraw the screen",
+    "  • Or press Tab and move with the board cursor:",
+    "    - Arrows or h/j/k/l: Move the cursor",
+    "    - Enter or Space: Select a piece, then its target (legal targets are marked)",
+    "    - q/r/b/n: Pick the promotion piece; Esc cancels, Tab goes back to typing",
+    "  • Or use the mouse: click a piece and then its target, or drag it there",
+    "  • Editing: ←/→ move the cursor, ↑/↓ recall commands, Esc clears the line",
+    "  • PgUp/PgDn or the mouse wheel scroll the move list",
+];
@@ -0,0 +1,842 @@
+use crate::chess::{Board, GameResult, GameState, Move, Player, STARTING_FEN};
+use crate::eco;
+use crate::engine::{EngineConfig, SearchLimit, StockfishEngine};
+use crate::pgn::{self, PgnGame, Termination};
+use crate::sprt::{Sprt, SprtConfig, SprtDecision};
+use crate::syzygy::Tablebase;
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(clap::Args)]
+pub struct MatchArgs {
+    /// Engine to play, given exactly twice: cmd=PATH[,name=NAME][,option.NAME=VALUE...]
+    #[arg(long = "engine", required = true)]
+    pub engines: Vec<String>,
+
+    /// Number of games to play [default: 10, or unlimited with --sprt]
+    #[arg(short = 'n', long)]
+    pub games: Option<usize>,
+
+    #[command(flatten)]
+    pub game: GameArgs,
+
+    /// Append finished games to this PGN file
+    #[arg(long)]
+    pub pgn_out: Option<PathBuf>,
+
+    #[arg(long, default_value = "chess-cli match")]
+    pub event: String,
+
+    /// Stop early by SPRT, e.g. --sprt elo0=0 elo1=5 alpha=0.05 beta=0.05
+    #[arg(long, num_args = 0.., value_name = "PARAM=VALUE")]
+    pub sprt: Option<Vec<String>>,
+}
+
+/// Game settings shared by every command that plays engines against each other.
+#[derive(clap::Args)]
+pub struct GameArgs {
+    /// Time control as [moves/]seconds[+increment], e.g. 40/60 or 10+0.1
+    #[arg(long, default_value = "10+0.1")]
+    pub tc: String,
+
+    /// Fixed time per move in milliseconds, instead of a time control
+    #[arg(long)]
+    pub movetime: Option<u64>,
+
+    /// Overshoot in milliseconds tolerated before a game is lost on time
+    #[arg(long, default_value = "0")]
+    pub time_margin: u64,
+
+    /// Opening suite (.epd or .pgn); each opening is played with both colors
+    #[arg(long)]
+    pub openings: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value = "sequential")]
+    pub opening_order: OpeningOrder,
+
+    /// Maximum number of plies taken from each PGN opening
+    #[arg(long, default_value = "16")]
+    pub opening_plies: usize,
+
+    /// Adjudicate a loss once a side's score stays below -SCORE centipawns...
+    #[arg(long)]
+    pub resign_score: Option<i32>,
+
+    /// ...for this many consecutive moves
+    #[arg(long, default_value = "3")]
+    pub resign_moves: u32,
+
+    /// Adjudicate a draw once both scores stay within SCORE centipawns...
+    #[arg(long)]
+    pub draw_score: Option<i32>,
+
+    /// ...for this many consecutive moves per side...
+    #[arg(long, default_value = "8")]
+    pub draw_moves: u32,
+
+    /// ...starting at this move number
+    #[arg(long, default_value = "40")]
+    pub draw_movenumber: u32,
+
+    /// Number of games played in parallel
+    #[arg(short, long, default_value = "1")]
+    pub concurrency: usize,
+
+    /// Syzygy tablebase directories: adjudicate by tablebase and pass SyzygyPath to the engines
+    #[arg(long)]
+    pub syzygy_path: Option<String>,
+}
+
+impl GameArgs {
+    /// The opening suite in playing order, or just the standard start position.
+    pub fn load_openings(&self) -> Result<Vec<Opening>> {
+        let mut openings = match self.openings {
+            Some(ref path) => load_openings(path, self.opening_plies)?,
+            None => vec![Opening::startpos()],
+        };
+        if let OpeningOrder::Random = self.opening_order {
+            fastrand::shuffle(&mut openings);
+        }
+        Ok(openings)
+    }
+
+    /// Parses `--engine` specs, adding `SyzygyPath` unless a spec sets it itself.
+    pub fn engine_configs(&self, specs: &[String]) -> Result<Vec<EngineConfig>> {
+        let mut configs = specs.iter().map(|spec| EngineConfig::parse(spec)).collect::<Result<Vec<_>>>()?;
+        if let Some(ref path) = self.syzygy_path {
+            for config in configs.iter_mut() {
+                if !config.options.iter().any(|(name, _)| name == "SyzygyPath") {
+                    config.options.push(("SyzygyPath".to_string(), path.clone()));
+                }
+            }
+        }
+        Ok(configs)
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OpeningOrder {
+    Sequential,
+    Random,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TimeControl {
+    pub moves: Option<u32>,
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid time control: {}", spec);
+        let (moves, rest) = match spec.split_once('/') {
+            Some((moves, rest)) => match moves.parse::<u32>() {
+                Ok(moves) if moves > 0 => (Some(moves), rest),
+                _ => return Err(invalid()),
+            },
+            None => (None, spec),
+        };
+        let (base, increment) = match rest.split_once('+') {
+            Some((base, increment)) => (base, increment),
+            None => (rest, "0"),
+        };
+
+        let seconds = |s: &str| -> Result<Duration> {
+            match s.parse::<f64>() {
+                Ok(value) if value.is_finite() && value >= 0.0 => Ok(Duration::from_secs_f64(value)),
+                _ => Err(invalid()),
+            }
+        };
+
+        Ok(TimeControl {
+            moves,
+            base: seconds(base)?,
+            increment: seconds(increment)?,
+        })
+    }
+
+    /// The time control in PGN `TimeControl` tag syntax.
+    pub fn to_pgn(self) -> String {
+        let base = self.base.as_secs_f64();
+        match (self.moves, self.increment.is_zero()) {
+            (Some(moves), _) => format!("{}/{}", moves, base),
+            (None, true) => format!("{}", base),
+            (None, false) => format!("{}+{}", base, self.increment.as_secs_f64()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Adjudication {
+    pub resign_score: Option<i32>,
+    pub resign_moves: u32,
+    pub draw_score: Option<i32>,
+    pub draw_moves: u32,
+    pub draw_movenumber: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameSettings {
+    pub time_control: Option<TimeControl>,
+    pub move_time: Option<Duration>,
+    pub time_margin: Duration,
+    pub adjudication: Adjudication,
+    pub tablebase: Option<Arc<Tablebase>>,
+    pub event: String,
+}
+
+impl GameSettings {
+    pub fn from_args(args: &GameArgs, event: &str) -> Result<Self> {
+        Ok(GameSettings {
+            time_control: match args.movetime {
+                Some(_) => None,
+                None => Some(TimeControl::parse(&args.tc)?),
+            },
+            move_time: args.movetime.map(Duration::from_millis),
+            time_margin: Duration::from_millis(args.time_margin),
+            adjudication: Adjudication {
+                resign_score: args.resign_score,
+                resign_moves: args.resign_moves,
+                draw_score: args.draw_score,
+                draw_moves: args.draw_moves,
+                draw_movenumber: args.draw_movenumber,
+            },
+            tablebase: match args.syzygy_path {
+                Some(ref path) => Some(Arc::new(Tablebase::open(path)?)),
+                None => None,
+            },
+            event: event.to_string(),
+        })
+    }
+
+    /// The time control in PGN `TimeControl` tag syntax.
+    pub fn time_control_tag(&self) -> String {
+        match (self.time_control, self.move_time) {
+            (Some(tc), _) => tc.to_pgn(),
+            (None, Some(move_time)) => format!("{}/move", move_time.as_secs_f64()),
+            (None, None) => "-".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Opening {
+    pub fen: String,
+    pub moves: Vec<Move>,
+}
+
+impl Opening {
+    pub fn startpos() -> Self {
+        Opening { fen: STARTING_FEN.to_string(), moves: Vec::new() }
+    }
+}
+
+/// Loads an EPD (one position per line) or PGN opening suite.
+pub fn load_openings(path: &Path, max_plies: usize) -> Result<Vec<Opening>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read openings '{}': {}", path.display(), e))?;
+
+    let is_pgn = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pgn"));
+    let mut openings = Vec::new();
+
+    if is_pgn {
+        for game in pgn::parse_games(&text)? {
+            let board = game.starting_board()?;
+            let mut moves = game.mainline_moves()?;
+            moves.truncate(max_plies);
+            openings.push(Opening { fen: board.to_fen(), moves });
+        }
+    } else {
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+            let fen = format!("{} 0 1", fields.join(" "));
+            Board::from_fen(&fen)?;
+            openings.push(Opening { fen, moves: Vec::new() });
+        }
+    }
+
+    if openings.is_empty() {
+        return Err(anyhow!("No openings found in '{}'", path.display()));
+    }
+    Ok(openings)
+}
+
+#[derive(Debug, Clone)]
+pub struct GameOutcome {
+    pub result: GameResult,
+    pub reason: String,
+    pub termination: Termination,
+}
+
+impl GameOutcome {
+    pub fn new(result: GameResult, reason: String, termination: Termination) -> Self {
+        GameOutcome { result, reason, termination }
+    }
+
+    fn loss(loser: Player, reason: &str, termination: Termination) -> Self {
+        GameOutcome::new(
+            GameResult::win_for(loser.opposite()),
+            format!("{} {}", player_name(loser), reason),
+            termination,
+        )
+    }
+}
+
+pub struct GameRecord {
+    pub outcome: GameOutcome,
+    pub pgn: PgnGame,
+}
+
+fn player_name(player: Player) -> &'static str {
+    match player {
+        Player::White => "White",
+        Player::Black => "Black",
+    }
+}
+
+/// Ends the game if the rules require it; engines are assumed to claim every draw.
+pub fn rules_outcome(board: &Board) -> Option<GameOutcome> {
+    let outcome = |result, reason: &str| Some(GameOutcome::new(result, reason.to_string(), Termination::Normal));
+
+    match board.game_state() {
+        GameState::Checkmate => {
+            let winner = board.current_player().opposite();
+            return outcome(GameResult::win_for(winner), &format!("{} mates", player_name(winner)));
+        }
+        GameState::Stalemate => return outcome(GameResult::Draw, "Draw by stalemate"),
+        GameState::Draw if board.is_insufficient_material() => {
+            return outcome(GameResult::Draw, "Draw by insufficient mating material");
+        }
+        _ => {}
+    }
+
+    if board.repetition_count() >= 3 {
+        return outcome(GameResult::Draw, "Draw by 3-fold repetition");
+    }
+    if board.halfmove_clock() >= 100 {
+        return outcome(GameResult::Draw, "Draw by fifty moves rule");
+    }
+    None
+}
+
+/// Adjudicates positions the tablebases cover; cursed wins count as draws.
+pub fn tablebase_outcome(tablebase: &Tablebase, board: &Board) -> Option<GameOutcome> {
+    let result = tablebase.probe_result(board)?;
+    let reason = match result {
+        GameResult::WhiteWins => "White wins by tablebase adjudication",
+        GameResult::BlackWins => "Black wins by tablebase adjudication",
+        GameResult::Draw => "Draw by tablebase adjudication",
+    };
+    Some(GameOutcome::new(result, reason.to_string(), Termination::Adjudication))
+}
+
+struct Adjudicator<'a> {
+    rules: &'a Adjudication,
+    draw_plies: u32,
+    resign_counts: [u32; 2],
+}
+
+impl<'a> Adjudicator<'a> {
+    fn new(rules: &'a Adjudication) -> Self {
+        Adjudicator { rules, draw_plies: 0, resign_counts: [0, 0] }
+    }
+
+    /// Feeds the mover's own evaluation after each move.
+    fn update(&mut self, mover: Player, score_cp: Option<i32>, move_number: u32) -> Option<GameOutcome> {
+        let score = match score_cp {
+            Some(score) => score,
+            None => {
+                self.draw_plies = 0;
+                self.resign_counts = [0, 0];
+                return None;
+            }
+        };
+
+        if let Some(threshold) = self.rules.resign_score {
+            let count = &mut self.resign_counts[mover as usize];
+            *count = if score <= -threshold { *count + 1 } else { 0 };
+            if *count >= self.rules.resign_moves {
+                let winner = mover.opposite();
+                return Some(GameOutcome::new(
+                    GameResult::win_for(winner),
+                    format!("{} wins by adjudication", player_name(winner)),
+                    Termination::Adjudication,
+                ));
+            }
+        }
+
+        if let Some(threshold) = self.rules.draw_score {
+            if move_number >= self.rules.draw_movenumber && score.abs() <= threshold {
+                self.draw_plies += 1;
+            } else {
+                self.draw_plies = 0;
+            }
+            if self.draw_plies >= 2 * self.rules.draw_moves {
+                return Some(GameOutcome::new(
+                    GameResult::Draw,
+                    "Draw by adjudication".to_string(),
+                    Termination::Adjudication,
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+// Fixed-time searches have no clock to lose on; only a reply this late counts as a hang.
+const MOVE_TIME_GRACE: Duration = Duration::from_secs(1);
+
+/// Plays one game between two already running engines and returns its record.
+pub fn play_game(
+    white: &mut StockfishEngine,
+    black: &mut StockfishEngine,
+    opening: &Opening,
+    settings: &GameSettings,
+) -> Result<GameRecord> {
+    white.new_game()?;
+    black.new_game()?;
+
+    let mut board = Board::from_fen(&opening.fen)?;
+    let mut pgn = PgnGame::new();
+    pgn.set_tag("Event", &settings.event);
+    pgn.set_tag("Site", "chess-cli");
+    pgn.set_tag("White", white.name());
+    pgn.set_tag("Black", black.name());
+    pgn.set_starting_fen(&opening.fen);
+    pgn.set_tag("TimeControl", &settings.time_control_tag());
+
+    let mut moves = Vec::new();
+    for chess_move in &opening.moves {
+        pgn.push_move(board.to_san(chess_move), None);
+        board.make_move(chess_move)?;
+        moves.push(chess_move.clone());
+    }
+
+    let base = settings.time_control.map_or(Duration::ZERO, |tc| tc.base);
+    let mut clocks = [base, base];
+    let mut moves_played = [0u32, 0u32];
+    let mut adjudicator = Adjudicator::new(&settings.adjudication);
+
+    let outcome = loop {
+        if let Some(outcome) = rules_outcome(&board) {
+            break outcome;
+        }
+        if let Some(outcome) = settings.tablebase.as_ref().and_then(|tb| tablebase_outcome(tb, &board)) {
+            break outcome;
+        }
+
+        let side = board.current_player();
+        let engine = match side {
+            Player::White => &mut *white,
+            Player::Black => &mut *black,
+        };
+
+        let limit = match (settings.time_control, settings.move_time) {
+            (Some(tc), _) => SearchLimit::Clock {
+                white_time: clocks[Player::White as usize],
+                black_time: clocks[Player::Black as usize],
+                white_increment: tc.increment,
+                black_increment: tc.increment,
+                moves_to_go: tc.moves.map(|moves| moves - moves_played[side as usize] % moves),
+            },
+            (None, move_time) => SearchLimit::MoveTime(move_time.unwrap_or(Duration::from_secs(1))),
+        };
+        // Stop waiting once the side's clock runs out, so a hung engine can't stall the match.
+        let timeout = match limit {
+            SearchLimit::MoveTime(move_time) => move_time + settings.time_margin.max(MOVE_TIME_GRACE),
+            SearchLimit::Clock { .. } => clocks[side as usize] + settings.time_margin,
+        };
+
+        let started = Instant::now();
+        let search = engine
+            .set_position(&opening.fen, &moves)
+            .and_then(|_| engine.go_within(&limit, timeout));
+        let elapsed = started.elapsed();
+
+        let search = match search {
+            Ok(Some(search)) => search,
+            Ok(None) => break GameOutcome::loss(side, "loses on time", Termination::TimeForfeit),
+            Err(_) => break GameOutcome::loss(side, "disconnects", Termination::Abandoned),
+        };
+
+        if let Some(tc) = settings.time_control {
+            let clock = &mut clocks[side as usize];
+            if elapsed > *clock + settings.time_margin {
+                break GameOutcome::loss(side, "loses on time", Termination::TimeForfeit);
+            }
+            *clock = clock.saturating_sub(elapsed) + tc.increment;
+            moves_played[side as usize] += 1;
+            if let Some(moves) = tc.moves {
+                if moves_played[side as usize].is_multiple_of(moves) {
+                    *clock += tc.base;
+                }
+            }
+        }
+
+        let chess_move = match search
+            .best_move
+            .and_then(|m| board.find_legal_move(m.from(), m.to(), m.promotion()))
+        {
+            Some(chess_move) => chess_move,
+            None => break GameOutcome::loss(side, "makes an illegal move", Termination::RulesInfraction),
+        };
+
+        let score = search.info.score.map(|s| s.as_centipawns());
+        let comment = match score {
+            Some(cp) => format!(
+                "{:+.2}/{} {:.3}s",
+                cp as f64 / 100.0,
+                search.info.depth,
+                elapsed.as_secs_f64()
+            ),
+            None => format!("{:.3}s", elapsed.as_secs_f64()),
+        };
+
+        pgn.push_move(board.to_san(&chess_move), Some(comment));
+        let move_number = board.move_count();
+        board.make_move(&chess_move)?;
+        moves.push(chess_move);
+
+        if let Some(outcome) = adjudicator.update(side, score, move_number) {
+            break outcome;
+        }
+    };
+
+    pgn.set_result(outcome.result);
+    pgn.set_tag("Termination", outcome.termination.to_pgn());
+    pgn.result_comment = Some(outcome.reason.clone());
+    eco::tag_game(&mut pgn);
+
+    Ok(GameRecord { outcome, pgn })
+}
+
+/// Wins, losses and draws from the first engine's point of view.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    /// Counts a game and returns the first engine's score from it.
+    pub fn add(&mut self, result: GameResult, first_engine_white: bool) -> f64 {
+        match (result, first_engine_white) {
+            (GameResult::Draw, _) => {
+                self.draws += 1;
+                0.5
+            }
+            (GameResult::WhiteWins, true) | (GameResult::BlackWins, false) => {
+                self.wins += 1;
+                1.0
+            }
+            _ => {
+                self.losses += 1;
+                0.0
+            }
+        }
+    }
+
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// Elo difference with its 95% confidence half-width, if the score is not 0% or 100%.
+    pub fn elo_difference(&self) -> Option<(f64, f64)> {
+        let games = self.games() as f64;
+        let score = self.score();
+        if self.games() == 0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games;
+        let margin = 1.959_964 * (variance / games).sqrt();
+        let low = (score - margin).max(1e-6);
+        let high = (score + margin).min(1.0 - 1e-6);
+
+        Some((elo_from_score(score), (elo_from_score(high) - elo_from_score(low)) / 2.0))
+    }
+}
+
+pub fn elo_from_score(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// One game to play: engines are indices into the configs.
+pub struct Pairing {
+    pub index: usize,
+    pub white: usize,
+    pub black: usize,
+    pub opening: Opening,
+}
+
+pub struct FinishedGame {
+    pub index: usize,
+    pub record: Result<GameRecord>,
+}
+
+/// Plays games from `next_game` on `concurrency` threads, passing each to `on_finished` as it ends.
+/// Every thread keeps its engines running between games.
+pub fn play_games(
+    configs: &[EngineConfig],
+    settings: &GameSettings,
+    concurrency: usize,
+    next_game: impl Fn() -> Option<Pairing> + Sync,
+    mut on_finished: impl FnMut(FinishedGame) -> Result<()>,
+) -> Result<()> {
+    thread::scope(|scope| -> Result<()> {
+        let (sender, receiver) = mpsc::channel();
+
+        for _ in 0..concurrency.max(1) {
+            let sender = sender.clone();
+            let next_game = &next_game;
+
+            scope.spawn(move || {
+                let mut pool: HashMap<usize, StockfishEngine> = HashMap::new();
+                while let Some(pairing) = next_game() {
+                    let record = play_pairing(&mut pool, configs, settings, &pairing);
+                    if sender.send(FinishedGame { index: pairing.index, record }).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        for finished in receiver {
+            on_finished(finished)?;
+        }
+        Ok(())
+    })
+}
+
+fn play_pairing(
+    pool: &mut HashMap<usize, StockfishEngine>,
+    configs: &[EngineConfig],
+    settings: &GameSettings,
+    pairing: &Pairing,
+) -> Result<GameRecord> {
+    let mut white = take_engine(pool, configs, pairing.white)?;
+    let mut black = take_engine(pool, configs, pairing.black)?;
+    let record = play_game(&mut white, &mut black, &pairing.opening, settings);
+    // Engines that failed or stopped answering are restarted for the next game.
+    if record.is_ok() && !white.is_hung() && !black.is_hung() {
+        pool.insert(pairing.white, white);
+        pool.insert(pairing.black, black);
+    }
+    record
+}
+
+fn take_engine(
+    pool: &mut HashMap<usize, StockfishEngine>,
+    configs: &[EngineConfig],
+    index: usize,
+) -> Result<StockfishEngine> {
+    match pool.remove(&index) {
+        Some(engine) => Ok(engine),
+        None => StockfishEngine::from_config(&configs[index]),
+    }
+}
+
+pub fn run(args: MatchArgs) -> Result<()> {
+    if args.engines.len() != 2 {
+        return Err(anyhow!("A match needs exactly two --engine specs, got {}", args.engines.len()));
+    }
+    let configs = args.game.engine_configs(&args.engines)?;
+    let settings = GameSettings::from_args(&args.game, &args.event)?;
+    let mut sprt = match args.sprt {
+        Some(ref params) => Some(Sprt::new(SprtConfig::parse(params)?)),
+        None => None,
+    };
+    let total_games = match (args.games, &sprt) {
+        (Some(games), _) => games,
+        (None, Some(_)) => usize::MAX,
+        (None, None) => 10,
+    };
+
+    let openings = args.game.load_openings()?;
+
+    let mut pgn_out = match args.pgn_out {
+        Some(ref path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+
+    let mut names = [
+        configs[0].name.clone().unwrap_or_else(|| configs[0].command.clone()),
+        configs[1].name.clone().unwrap_or_else(|| configs[1].command.clone()),
+    ];
+    if sprt.is_some() {
+        println!("🏟️  SPRT match: {} vs {}", names[0], names[1]);
+    } else {
+        println!("🏟️  Match: {} vs {} ({} games)", names[0], names[1], total_games);
+    }
+
+    let next_game = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let mut decision = None;
+    let mut score = MatchScore::default();
+
+    // Consecutive games share an opening with colors reversed.
+    let next_pairing = || {
+        if stop.load(Ordering::SeqCst) {
+            return None;
+        }
+        let index = next_game.fetch_add(1, Ordering::SeqCst);
+        if index >= total_games {
+            return None;
+        }
+        let (white, black) = if index.is_multiple_of(2) { (0, 1) } else { (1, 0) };
+        let opening = openings[(index / 2) % openings.len()].clone();
+        Some(Pairing { index, white, black, opening })
+    };
+
+    play_games(&configs, &settings, args.game.concurrency, next_pairing, |finished| {
+        let first_engine_white = finished.index.is_multiple_of(2);
+        let mut record = match finished.record {
+            Ok(record) => record,
+            Err(e) => {
+                println!("❌ Game {} failed: {}", finished.index + 1, e);
+                if let Some(ref mut sprt) = sprt {
+                    sprt.discard(finished.index);
+                }
+                return Ok(());
+            }
+        };
+
+        record.pgn.set_tag("Round", &(finished.index + 1).to_string());
+        let game_score = score.add(record.outcome.result, first_engine_white);
+
+        // Prefer the names the engines reported in their handshake.
+        let (first_tag, second_tag) = if first_engine_white { ("White", "Black") } else { ("Black", "White") };
+        for (name, tag) in names.iter_mut().zip([first_tag, second_tag]) {
+            if let Some(reported) = record.pgn.tag(tag) {
+                *name = reported.to_string();
+            }
+        }
+
+        // With SPRT a single live progress line replaces the per-game output.
+        match sprt {
+            // Games still in flight after a decision are recorded but no longer tested.
+            Some(ref mut sprt) if decision.is_none() => {
+                sprt.record(finished.index, game_score);
+                print!("\r{}", sprt.progress_line(&score));
+                io::stdout().flush()?;
+                decision = sprt.decision();
+                if decision.is_some() {
+                    stop.store(true, Ordering::SeqCst);
+                }
+            }
+            Some(_) => {}
+            None => {
+                println!(
+                    "Finished game {} ({} vs {}): {} {{{}}}",
+                    finished.index + 1,
+                    record.pgn.tag("White").unwrap_or("?"),
+                    record.pgn.tag("Black").unwrap_or("?"),
+                    record.outcome.result.to_pgn(),
+                    record.outcome.reason
+                );
+                print_score(&names, &score);
+            }
+        }
+
+        if let Some(ref mut file) = pgn_out {
+            write_pgn(file, &record.pgn)?;
+        }
+        Ok(())
+    })?;
+
+    println!();
+    print_score(&names, &score);
+    match score.elo_difference() {
+        Some((elo, error)) => println!("Elo difference: {:.1} +/- {:.1}", elo, error),
+        None => println!("Elo difference: n/a"),
+    }
+    if let Some(ref sprt) = sprt {
+        let p = sprt.pentanomial();
+        println!("Ptnml(0-2): {}, {}, {}, {}, {}", p[0], p[1], p[2], p[3], p[4]);
+        match decision {
+            Some(SprtDecision::AcceptH1) => println!("SPRT: H1 was accepted (LLR {:.2})", sprt.llr()),
+            Some(SprtDecision::AcceptH0) => println!("SPRT: H0 was accepted (LLR {:.2})", sprt.llr()),
+            None => println!("SPRT: no decision (LLR {:.2})", sprt.llr()),
+        }
+    }
+
+    Ok(())
+}
+
+fn print_score(names: &[String; 2], score: &MatchScore) {
+    println!(
+        "Score of {} vs {}: {} - {} - {}  [{:.3}] {}",
+        names[0],
+        names[1],
+        score.wins,
+        score.losses,
+        score.draws,
+        score.score(),
+        score.games()
+    );
+}
+
+pub fn write_pgn(file: &mut File, game: &PgnGame) -> Result<()> {
+    file.write_all(game.to_pgn().as_bytes())?;
+    file.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_controls_parse() {
+        let tc = TimeControl::parse("40/60").unwrap();
+        assert_eq!((tc.moves, tc.base, tc.increment), (Some(40), Duration::from_secs(60), Duration::ZERO));
+        let tc = TimeControl::parse("10+0.1").unwrap();
+        assert_eq!((tc.moves, tc.base, tc.increment), (None, Duration::from_secs(10), Duration::from_millis(100)));
+        assert_eq!(TimeControl::parse("0").unwrap().base, Duration::ZERO);
+    }
+
+    #[test]
+    fn zero_moves_per_period_is_rejected() {
+        let error = TimeControl::parse("0/60").unwrap_err();
+        assert_eq!(error.to_string(), "Invalid time control: 0/60");
+        assert!(TimeControl::parse("x/60").is_err());
+    }
+
+    #[test]
+    fn negative_seconds_are_rejected() {
+        assert!(TimeControl::parse("-5").is_err());
+        assert!(TimeControl::parse("10+-1").is_err());
+        assert!(TimeControl::parse("40/-60").is_err());
+    }
+
+    #[test]
+    fn non_finite_seconds_are_rejected() {
+        for spec in ["inf", "NaN", "10+nan", "10+inf", "40/infinity"] {
+            let error = TimeControl::parse(spec).unwrap_err();
+            assert_eq!(error.to_string(), format!("Invalid time control: {}", spec));
+        }
+    }
+}
@@ -0,0 +1,600 @@
+use crate::chess::{Board, GameResult, Move, Player, STARTING_FEN};
+use anyhow::{anyhow, Result};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+const MAX_LINE_LENGTH: usize = 79;
+const NULL_MOVE: &str = "--";
+
+/// Values of the PGN `Termination` tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    Normal,
+    TimeForfeit,
+    Adjudication,
+    RulesInfraction,
+    Abandoned,
+    Unterminated,
+}
+
+impl Termination {
+    pub fn to_pgn(self) -> &'static str {
+        match self {
+            Termination::Normal => "normal",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::Adjudication => "adjudication",
+            Termination::RulesInfraction => "rules infraction",
+            Termination::Abandoned => "abandoned",
+            Termination::Unterminated => "unterminated",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PgnMove {
+    pub san: String,
+    pub nags: Vec<u8>,
+    /// Comment before the move; only written for the first move of a line.
+    pub starting_comment: Option<String>,
+    pub comment: Option<String>,
+    /// Alternatives to this move, each played from the position before it.
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    pub result_comment: Option<String>,
+}
+
+impl PgnGame {
+    pub fn new() -> Self {
+        let mut game = PgnGame {
+            tags: Vec::new(),
+            moves: Vec::new(),
+            result_comment: None,
+        };
+        for name in SEVEN_TAG_ROSTER {
+            game.set_tag(name, "?");
+        }
+        game.set_tag("Date", &today());
+        game.set_tag("Result", "*");
+        game
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(key, _)| key == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Records a non-standard starting position via the `SetUp`/`FEN` tag pair.
+    pub fn set_starting_fen(&mut self, fen: &str) {
+        if fen != STARTING_FEN {
+            self.set_tag("SetUp", "1");
+            self.set_tag("FEN", fen);
+        }
+    }
+
+    pub fn push_move(&mut self, san: String, comment: Option<String>) {
+        self.moves.push(PgnMove {
+            san,
+            comment,
+            ..PgnMove::default()
+        });
+    }
+
+    pub fn set_result(&mut self, result: GameResult) {
+        self.set_tag("Result", result.to_pgn());
+    }
+
+    pub fn starting_board(&self) -> Result<Board> {
+        match self.tag("FEN") {
+            Some(fen) => Board::from_fen(fen),
+            None => Ok(Board::new()),
+        }
+    }
+
+    /// Replays the movetext from the starting position, validating every move.
+    pub fn mainline_moves(&self) -> Result<Vec<Move>> {
+        let mut board = self.starting_board()?;
+        let mut moves = Vec::with_capacity(self.moves.len());
+        for pgn_move in &self.moves {
+            let chess_move = board.parse_san(&pgn_move.san)?;
+            board.make_move(&chess_move)?;
+            moves.push(chess_move);
+        }
+        Ok(moves)
+    }
+
+    /// The position before each mainline move, followed by the final position.
+    pub fn mainline_boards(&self) -> Result<Vec<Board>> {
+        let mut board = self.starting_board()?;
+        let mut boards = Vec::with_capacity(self.moves.len() + 1);
+        for pgn_move in &self.moves {
+            if pgn_move.san == NULL_MOVE {
+                return Err(anyhow!("Move {}: null moves can only appear in variations", board.move_count()));
+            }
+            let chess_move = board
+                .parse_san(&pgn_move.san)
+                .map_err(|e| anyhow!("Move {} ({}): {}", board.move_count(), pgn_move.san, e))?;
+            boards.push(board.clone());
+            board.make_move(&chess_move)?;
+        }
+        boards.push(board);
+        Ok(boards)
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut output = String::new();
+
+        let roster = SEVEN_TAG_ROSTER.iter().filter_map(|name| self.tags.iter().find(|(key, _)| key == name));
+        let others = self.tags.iter().filter(|(key, _)| !SEVEN_TAG_ROSTER.contains(&key.as_str()));
+        for (name, value) in roster.chain(others) {
+            output.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(value)));
+        }
+        output.push('\n');
+
+        let (move_number, white_to_move) = match self.starting_board() {
+            Ok(board) => (board.move_count(), board.current_player() == Player::White),
+            Err(_) => (1, true),
+        };
+
+        let mut tokens = Vec::new();
+        push_movetext(&mut tokens, &self.moves, move_number, white_to_move);
+        if let Some(ref comment) = self.result_comment {
+            tokens.push(format!("{{{}}}", comment));
+        }
+        tokens.push(self.tag("Result").unwrap_or("*").to_string());
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                output.push('\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                output.push(' ');
+                line_length += 1;
+            }
+            line_length += token.len();
+            output.push_str(&token);
+        }
+        output.push_str("\n\n");
+
+        output
+    }
+}
+
+/// Movetext for a line of moves, e.g. a variation starting at `move_number`.
+pub fn movetext(moves: &[PgnMove], move_number: u32, white_to_move: bool) -> String {
+    let mut tokens = Vec::new();
+    push_movetext(&mut tokens, moves, move_number, white_to_move);
+    tokens.join(" ")
+}
+
+fn push_movetext(tokens: &mut Vec<String>, moves: &[PgnMove], mut move_number: u32, mut white_to_move: bool) {
+    let mut needs_number = true;
+    for pgn_move in moves {
+        if let Some(ref comment) = pgn_move.starting_comment {
+            tokens.push(format!("{{{}}}", comment));
+        }
+        if white_to_move {
+            tokens.push(format!("{}.", move_number));
+        } else if needs_number {
+            tokens.push(format!("{}...", move_number));
+        }
+        tokens.push(pgn_move.san.clone());
+        tokens.extend(pgn_move.nags.iter().map(|nag| format!("${}", nag)));
+        needs_number = false;
+
+        if let Some(ref comment) = pgn_move.comment {
+            tokens.push(format!("{{{}}}", comment));
+            needs_number = true;
+        }
+        for variation in pgn_move.variations.iter().filter(|v| !v.is_empty()) {
+            let start = tokens.len();
+            push_movetext(tokens, variation, move_number, white_to_move);
+            tokens[start].insert(0, '(');
+            tokens.last_mut().unwrap().push(')');
+            needs_number = true;
+        }
+
+        if !white_to_move {
+            move_number += 1;
+        }
+        white_to_move = !white_to_move;
+    }
+}
+
+/// A comment without embedded commands such as `[%eval 0.35]` or `[%clk 0:05:00]`.
+pub fn strip_commands(comment: &str) -> String {
+    let mut text = String::new();
+    let mut rest = comment;
+    while let Some(start) = rest.find("[%") {
+        text.push_str(&rest[..start]);
+        rest = match rest[start..].find(']') {
+            Some(end) => &rest[start + end + 1..],
+            None => "",
+        };
+    }
+    text.push_str(rest);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Short glyph for the common NAGs, e.g. `$1` is `!` and `$16` is `±`.
+pub fn nag_glyph(nag: u8) -> Option<&'static str> {
+    let glyph = match nag {
+        1 => "!",
+        2 => "?",
+        3 => "!!",
+        4 => "??",
+        5 => "!?",
+        6 => "?!",
+        10 => "=",
+        13 => "∞",
+        14 => "⩲",
+        15 => "⩱",
+        16 => "±",
+        17 => "∓",
+        18 => "+-",
+        19 => "-+",
+        _ => return None,
+    };
+    Some(glyph)
+}
+
+/// The NAG for a glyph written as its own movetext token, as ChessBase and Lichess exports do.
+fn glyph_nag(glyph: &str) -> Option<u8> {
+    let nag = match glyph {
+        "□" => 7,
+        "=" => 10,
+        "∞" => 13,
+        "+=" | "⩲" => 14,
+        "=+" | "⩱" => 15,
+        "+/-" | "±" => 16,
+        "-/+" | "∓" => 17,
+        "+-" | "+−" => 18,
+        "-+" | "−+" => 19,
+        "⨀" => 22,
+        "=/∞" | "=∞" => 44,
+        "N" => 146,
+        _ => suffix_nag(glyph)?,
+    };
+    Some(nag)
+}
+
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+/// A line of movetext being parsed: the mainline or an open variation.
+#[derive(Default)]
+struct Line {
+    moves: Vec<PgnMove>,
+    starting_comment: Option<String>,
+}
+
+impl Line {
+    fn add_comment(&mut self, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        let slot = match self.moves.last_mut() {
+            Some(last) => &mut last.comment,
+            None => &mut self.starting_comment,
+        };
+        match slot {
+            Some(existing) => {
+                existing.push(' ');
+                existing.push_str(text);
+            }
+            None => *slot = Some(text.to_string()),
+        }
+    }
+}
+
+/// Collects the parsed movetext into the game, closing any unterminated variations.
+fn finish_game(mut game: PgnGame, mut lines: Vec<Line>) -> PgnGame {
+    while lines.len() > 1 {
+        let variation = lines.pop().unwrap();
+        if let Some(last) = lines.last_mut().and_then(|line| line.moves.last_mut()) {
+            last.variations.push(variation.moves);
+        }
+    }
+    let mainline = lines.pop().unwrap_or_default();
+    if mainline.moves.is_empty() {
+        game.result_comment = mainline.starting_comment;
+    }
+    game.moves = mainline.moves;
+    game
+}
+
+/// Splits a PGN database into games with their comments, NAGs and variations.
+pub fn parse_games(text: &str) -> Result<Vec<PgnGame>> {
+    let mut games = Vec::new();
+    let mut current: Option<PgnGame> = None;
+    let mut lines = vec![Line::default()];
+    let mut comment: Option<String> = None;
+
+    for (line_number, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('%') && comment.is_none() {
+            continue;
+        }
+
+        if comment.is_none() && lines.len() == 1 && trimmed.starts_with('[') && trimmed.ends_with(']') {
+            // A tag after movetext without a result token starts the next game.
+            if !lines[0].moves.is_empty() {
+                let game = current.take().unwrap_or_else(empty_game);
+                games.push(finish_game(game, std::mem::replace(&mut lines, vec![Line::default()])));
+            }
+            let (name, value) = parse_tag(trimmed)?;
+            current.get_or_insert_with(empty_game).set_tag(&name, &value);
+            continue;
+        }
+
+        let mut chars = trimmed.chars().peekable();
+        while let Some(c) = chars.next() {
+            if let Some(ref mut text) = comment {
+                if c == '}' {
+                    lines.last_mut().unwrap().add_comment(text);
+                    comment = None;
+                } else {
+                    text.push(c);
+                }
+                continue;
+            }
+            match c {
+                '{' => comment = Some(String::new()),
+                ';' => {
+                    let rest: String = chars.by_ref().collect();
+                    lines.last_mut().unwrap().add_comment(&rest);
+                }
+                '(' => {
+                    if lines.last().unwrap().moves.is_empty() {
+                        return Err(anyhow!("Line {}: variation before any move", line_number + 1));
+                    }
+                    lines.push(Line::default());
+                }
+                ')' => {
+                    if lines.len() == 1 {
+                        return Err(anyhow!("Line {}: unmatched ')'", line_number + 1));
+                    }
+                    let variation = lines.pop().unwrap();
+                    if !variation.moves.is_empty() {
+                        let parent = lines.last_mut().unwrap().moves.last_mut().unwrap();
+                        parent.variations.push(variation.moves);
+                    }
+                }
+                c if c.is_whitespace() => {}
+                _ => {
+                    let mut token = c.to_string();
+                    while let Some(&next) = chars.peek() {
+                        if next.is_whitespace() || "{}();".contains(next) {
+                            break;
+                        }
+                        token.push(next);
+                        chars.next();
+                    }
+                    match token.as_str() {
+                        "1-0" | "0-1" | "1/2-1/2" | "*" if lines.len() > 1 => {}
+                        "1-0" | "0-1" | "1/2-1/2" | "*" => {
+                            let mut game = current.take().unwrap_or_else(empty_game);
+                            game.set_tag("Result", &token);
+                            games.push(finish_game(game, std::mem::replace(&mut lines, vec![Line::default()])));
+                        }
+                        _ if glyph_nag(&token).is_some() => {
+                            if let Some(last) = lines.last_mut().unwrap().moves.last_mut() {
+                                last.nags.extend(glyph_nag(&token));
+                            }
+                        }
+                        _ if token.starts_with('$') => {
+                            let line = lines.last_mut().unwrap();
+                            let nag = token[1..]
+                                .parse()
+                                .map_err(|_| anyhow!("Line {}: invalid NAG '{}'", line_number + 1, token))?;
+                            if let Some(last) = line.moves.last_mut() {
+                                last.nags.push(nag);
+                            }
+                        }
+                        _ => {
+                            let line = lines.last_mut().unwrap();
+                            let san = match token.rfind('.') {
+                                Some(dot) => &token[dot + 1..],
+                                None if token.chars().all(|c| c.is_ascii_digit()) => "",
+                                None => &token,
+                            };
+                            let move_text = san.trim_end_matches(['!', '?']);
+                            let nag = suffix_nag(&san[move_text.len()..]);
+                            if move_text.is_empty() {
+                                if let (Some(nag), Some(last)) = (nag, line.moves.last_mut()) {
+                                    last.nags.push(nag);
+                                }
+                                continue;
+                            }
+                            // Null moves appear in analysis; they are kept for writing the game back out.
+                            let move_text = if matches!(move_text, "--" | "Z0") { NULL_MOVE } else { move_text };
+                            line.moves.push(PgnMove {
+                                san: move_text.to_string(),
+                                nags: nag.into_iter().collect(),
+                                starting_comment: if line.moves.is_empty() { line.starting_comment.take() } else { None },
+                                ..PgnMove::default()
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(ref mut text) = comment {
+            text.push(' ');
+        }
+    }
+
+    if !lines[0].moves.is_empty() {
+        games.push(finish_game(current.unwrap_or_else(empty_game), lines));
+    }
+
+    Ok(games)
+}
+
+fn empty_game() -> PgnGame {
+    PgnGame {
+        tags: Vec::new(),
+        moves: Vec::new(),
+        result_comment: None,
+    }
+}
+
+fn parse_tag(line: &str) -> Result<(String, String)> {
+    let inner = &line[1..line.len() - 1];
+    let (name, rest) = inner
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| anyhow!("Malformed PGN tag: {}", line))?;
+    let value = rest.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(|| anyhow!("Malformed PGN tag value: {}", line))?;
+    Ok((name.to_string(), value.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Today's date in the PGN `YYYY.MM.DD` format (UTC).
+pub fn today() -> String {
+    let (year, month, day) = civil_date(unix_seconds() / 86_400);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// Current UTC date and time as `YYYY-MM-DD_HHMMSS`, suitable for file names.
+pub fn timestamp() -> String {
+    let seconds = unix_seconds();
+    let (year, month, day) = civil_date(seconds / 86_400);
+    let time = seconds.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}_{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+fn unix_seconds() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0) as i64
+}
+
+fn civil_date(days: i64) -> (i64, i64, i64) {
+    // Civil-from-days conversion for the proleptic Gregorian calendar.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sans(moves: &[PgnMove]) -> Vec<&str> {
+        moves.iter().map(|m| m.san.as_str()).collect()
+    }
+
+    #[test]
+    fn comments_attach_to_the_move_before_them() {
+        let text = "{Opening remark} 1. e4 {King's pawn} e5 ; rest of the line\n2. Nf3 {multi\nline} *";
+        let game = &parse_games(text).unwrap()[0];
+        assert_eq!(sans(&game.moves), ["e4", "e5", "Nf3"]);
+        assert_eq!(game.moves[0].starting_comment.as_deref(), Some("Opening remark"));
+        assert_eq!(game.moves[0].comment.as_deref(), Some("King's pawn"));
+        assert_eq!(game.moves[1].comment.as_deref(), Some("rest of the line"));
+        assert_eq!(game.moves[2].comment.as_deref(), Some("multi line"));
+    }
+
+    #[test]
+    fn nags_suffixes_and_glyphs() {
+        let text = "1. e4! $14 e5?! 2. Nf3 += Nc6 N 3. Bb5 ± a6 +- 4. Ba4 ∞ Nf6 -+ 5. O-O = *";
+        let game = &parse_games(text).unwrap()[0];
+        let nags: Vec<&[u8]> = game.moves.iter().map(|m| m.nags.as_slice()).collect();
+        assert_eq!(nags, [&[1, 14][..], &[6], &[14], &[146], &[16], &[18], &[13], &[19], &[10]]);
+        assert_eq!(game.mainline_boards().unwrap().len(), 10);
+    }
+
+    #[test]
+    fn nested_variations_and_null_moves() {
+        let text = "1. e4 e5 (1... c5 2. Nf3 (2. c3 d5) d6) (1... e6 2. -- d5) 2. Nf3 *";
+        let game = &parse_games(text).unwrap()[0];
+        assert_eq!(sans(&game.moves), ["e4", "e5", "Nf3"]);
+        let variations = &game.moves[1].variations;
+        assert_eq!(variations.len(), 2);
+        assert_eq!(sans(&variations[0]), ["c5", "Nf3", "d6"]);
+        assert_eq!(sans(&variations[0][1].variations[0]), ["c3", "d5"]);
+        assert_eq!(sans(&variations[1]), ["e6", "--", "d5"]);
+        assert!(game.mainline_boards().is_ok());
+
+        assert!(parse_games("1. e4 ) *").is_err());
+        assert!(parse_games("(1. e4) *").is_err());
+    }
+
+    #[test]
+    fn move_numbers_in_every_form() {
+        let text = "1.e4 1... e5 2. Nf3 2...Nc6 3 . Bb5 3... a6 *";
+        let game = &parse_games(text).unwrap()[0];
+        assert_eq!(sans(&game.moves), ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]);
+    }
+
+    #[test]
+    fn multi_game_files() {
+        let text = "[Event \"One\"]\n[White \"A \\\"Q\\\" B\"]\n\n1. d4 d5 1-0\n\n\
+                    [Event \"Two\"]\n\n1. c4 {done} 0-1\n\n[Event \"Three\"]\n1. Nf3";
+        let games = parse_games(text).unwrap();
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].tag("Event"), Some("One"));
+        assert_eq!(games[0].tag("White"), Some("A \"Q\" B"));
+        assert_eq!(games[0].tag("Result"), Some("1-0"));
+        assert_eq!(sans(&games[1].moves), ["c4"]);
+        assert_eq!(games[1].tag("Result"), Some("0-1"));
+        assert_eq!(sans(&games[2].moves), ["Nf3"]);
+    }
+
+    #[test]
+    fn written_games_parse_back() {
+        let text = "1. e4 $1 {best} (1. d4 d5) 1... c5 2. Nf3 $18 *";
+        let game = &parse_games(text).unwrap()[0];
+        let again = &parse_games(&game.to_pgn()).unwrap()[0];
+        assert_eq!(sans(&again.moves), sans(&game.moves));
+        assert_eq!(again.moves[0].nags, [1]);
+        assert_eq!(again.moves[0].comment.as_deref(), Some("best"));
+        assert_eq!(sans(&again.moves[0].variations[0]), ["d4", "d5"]);
+        assert_eq!(again.moves[2].nags, [18]);
+    }
+}
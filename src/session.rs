@@ -0,0 +1,101 @@
+use crate::book::BookSelection;
+use crate::chess::{Board, Move};
+use crate::engine::Score;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// An interactive game in progress, with everything needed to continue it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedGame {
+    pub start_fen: String,
+    /// Moves played so far, in UCI notation.
+    pub moves: Vec<String>,
+    pub player_white: bool,
+    /// Thinking time used by White and Black, in milliseconds.
+    pub clocks_ms: [u64; 2],
+    pub engine_path: String,
+    pub time_limit: u64,
+    pub book: Option<PathBuf>,
+    pub book_selection: BookSelection,
+    pub book_depth: u32,
+    pub syzygy_path: Option<String>,
+    /// Engine time per hint, in milliseconds.
+    #[serde(default = "default_hint_time")]
+    pub hint_time: u64,
+    /// Number of positions in which the player asked for a hint.
+    #[serde(default)]
+    pub hints: u32,
+    #[serde(default)]
+    pub takebacks: Vec<Takeback>,
+    /// Thinking time and evaluation of each move; may run past `moves` after a takeback, for redo.
+    #[serde(default)]
+    pub notes: Vec<MoveNote>,
+}
+
+fn default_hint_time() -> u64 {
+    300
+}
+
+/// What is known about a move besides the move itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MoveNote {
+    /// Thinking time, in milliseconds.
+    pub time_ms: Option<u64>,
+    /// The engine's evaluation after the move, from White's side.
+    pub eval: Option<Score>,
+}
+
+/// Moves the player took back, kept so the PGN can mention them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Takeback {
+    /// Number of moves left on the board after the takeback.
+    pub ply: usize,
+    /// The moves taken back, as movetext.
+    pub moves: String,
+}
+
+impl SavedGame {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read saved game '{}': {}", path.display(), e))?;
+        serde_json::from_str(&text).map_err(|e| anyhow!("Invalid saved game '{}': {}", path.display(), e))
+    }
+
+    // Write-then-rename so an interruption never leaves a truncated file behind.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    /// Plays the saved moves from the start position, checking each one.
+    pub fn replay(&self) -> Result<Board> {
+        let mut board = Board::from_fen(&self.start_fen)?;
+        for uci in &self.moves {
+            let parsed = Move::from_uci(uci)?;
+            let chess_move = board
+                .find_legal_move(parsed.from(), parsed.to(), parsed.promotion())
+                .ok_or_else(|| anyhow!("Saved move {} is illegal", uci))?;
+            board.make_move(&chess_move)?;
+        }
+        Ok(board)
+    }
+}
+
+/// Per-user directory for saved games and PGN files.
+pub fn data_dir() -> Result<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join("chess-cli"))
+        .ok_or_else(|| anyhow!("No data directory found for this user"))
+}
+
+/// Where the game in progress is saved on exit and restored by `--resume`.
+pub fn autosave_path() -> Result<PathBuf> {
+    Ok(data_dir()?.join("autosave.json"))
+}
@@ -0,0 +1,255 @@
+use crate::match_runner::MatchScore;
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+
+/// Hypotheses and error rates for a sequential probability ratio test.
+/// Elo bounds are logistic Elo differences of the first engine over the second.
+#[derive(Debug, Clone, Copy)]
+pub struct SprtConfig {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl SprtConfig {
+    /// Parses `elo0=0 elo1=5 alpha=0.05 beta=0.05`; omitted values keep these defaults.
+    pub fn parse(params: &[String]) -> Result<Self> {
+        let mut config = SprtConfig { elo0: 0.0, elo1: 5.0, alpha: 0.05, beta: 0.05 };
+
+        for param in params {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected key=value in SPRT parameters, got '{}'", param))?;
+            let value: f64 = value
+                .parse()
+                .map_err(|_| anyhow!("Invalid SPRT value for {}: {}", key, value))?;
+            match key {
+                "elo0" => config.elo0 = value,
+                "elo1" => config.elo1 = value,
+                "alpha" => config.alpha = value,
+                "beta" => config.beta = value,
+                _ => return Err(anyhow!("Unknown SPRT parameter '{}'", key)),
+            }
+        }
+
+        if config.elo0 >= config.elo1 {
+            return Err(anyhow!("SPRT needs elo0 < elo1"));
+        }
+        let error_rate = |p: f64| p > 0.0 && p < 0.5;
+        if !error_rate(config.alpha) || !error_rate(config.beta) {
+            return Err(anyhow!("SPRT alpha and beta must lie in (0, 0.5)"));
+        }
+        Ok(config)
+    }
+
+    /// Log-likelihood ratio bounds: (accept H0 below, accept H1 above).
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtDecision {
+    AcceptH0,
+    AcceptH1,
+}
+
+/// Running SPRT over game pairs: games 2k and 2k+1 share an opening with colors reversed.
+pub struct Sprt {
+    config: SprtConfig,
+    pentanomial: [u32; 5],
+    pending: HashMap<usize, f64>,
+    // Pairs with a failed game, whose other game no longer counts.
+    dropped: HashSet<usize>,
+}
+
+impl Sprt {
+    pub fn new(config: SprtConfig) -> Self {
+        Sprt { config, pentanomial: [0; 5], pending: HashMap::new(), dropped: HashSet::new() }
+    }
+
+    /// Records one game's score (1, 0.5 or 0) for the first engine.
+    pub fn record(&mut self, game_index: usize, score: f64) {
+        let pair = game_index / 2;
+        if self.dropped.remove(&pair) {
+            return;
+        }
+        match self.pending.remove(&pair) {
+            Some(other) => self.pentanomial[((score + other) * 2.0).round() as usize] += 1,
+            None => {
+                self.pending.insert(pair, score);
+            }
+        }
+    }
+
+    /// Drops the pair of a game that failed to finish, so it doesn't wait for it forever.
+    pub fn discard(&mut self, game_index: usize) {
+        let pair = game_index / 2;
+        if self.pending.remove(&pair).is_none() {
+            self.dropped.insert(pair);
+        }
+    }
+
+    pub fn pentanomial(&self) -> [u32; 5] {
+        self.pentanomial
+    }
+
+    /// Generalized SPRT log-likelihood ratio on the pentanomial pair scores.
+    pub fn llr(&self) -> f64 {
+        // Until two different pair outcomes are seen the variance estimate is meaningless.
+        if self.pentanomial.iter().filter(|&&c| c > 0).count() < 2 {
+            return 0.0;
+        }
+        let pairs: u32 = self.pentanomial.iter().sum();
+
+        // A small prior keeps the variance positive while some outcomes are unseen.
+        let counts: Vec<f64> = self.pentanomial.iter().map(|&c| c as f64 + 1e-3).collect();
+        let total: f64 = counts.iter().sum();
+        let outcomes = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+        let mean: f64 = counts.iter().zip(outcomes).map(|(c, s)| c * s).sum::<f64>() / total;
+        let variance: f64 = counts
+            .iter()
+            .zip(outcomes)
+            .map(|(c, s)| c * (s - mean).powi(2))
+            .sum::<f64>()
+            / total;
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let score0 = score_from_elo(self.config.elo0);
+        let score1 = score_from_elo(self.config.elo1);
+        pairs as f64 * (score1 - score0) * (2.0 * mean - score0 - score1) / (2.0 * variance)
+    }
+
+    pub fn decision(&self) -> Option<SprtDecision> {
+        let (lower, upper) = self.config.bounds();
+        let llr = self.llr();
+        if llr <= lower {
+            Some(SprtDecision::AcceptH0)
+        } else if llr >= upper {
+            Some(SprtDecision::AcceptH1)
+        } else {
+            None
+        }
+    }
+
+    pub fn progress_line(&self, score: &MatchScore) -> String {
+        let (lower, upper) = self.config.bounds();
+        let p = self.pentanomial;
+        format!(
+            "LLR: {:.2} ({:.2}, {:.2}) [{:.2}, {:.2}] | Games: {} W: {} L: {} D: {} | Ptnml(0-2): {}, {}, {}, {}, {}",
+            self.llr(),
+            lower,
+            upper,
+            self.config.elo0,
+            self.config.elo1,
+            score.games(),
+            score.wins,
+            score.losses,
+            score.draws,
+            p[0],
+            p[1],
+            p[2],
+            p[3],
+            p[4]
+        )
+    }
+}
+
+fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(elo0: f64, elo1: f64) -> SprtConfig {
+        SprtConfig { elo0, elo1, alpha: 0.05, beta: 0.05 }
+    }
+
+    // Feeds game pairs whose scores add up to 0, 0.5, 1, 1.5 and 2 points.
+    fn sprt_with(config: SprtConfig, pentanomial: [u32; 5]) -> Sprt {
+        let pairs = [(0.0, 0.0), (0.0, 0.5), (0.5, 0.5), (1.0, 0.5), (1.0, 1.0)];
+        let mut sprt = Sprt::new(config);
+        let mut game = 0;
+        for (count, (first, second)) in pentanomial.into_iter().zip(pairs) {
+            for _ in 0..count {
+                sprt.record(game, first);
+                sprt.record(game + 1, second);
+                game += 2;
+            }
+        }
+        assert_eq!(sprt.pentanomial(), pentanomial);
+        sprt
+    }
+
+    fn params(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn bounds_follow_the_error_rates() {
+        let (lower, upper) = config(0.0, 5.0).bounds();
+        assert!((lower + 2.944_439).abs() < 1e-6);
+        assert!((upper - 2.944_439).abs() < 1e-6);
+
+        let config = SprtConfig { alpha: 0.05, beta: 0.1, ..config(0.0, 5.0) };
+        let (lower, upper) = config.bounds();
+        assert!((lower + 2.251_292).abs() < 1e-6);
+        assert!((upper - 2.890_372).abs() < 1e-6);
+    }
+
+    #[test]
+    fn llr_matches_reference_values() {
+        let cases = [
+            (config(0.0, 5.0), [100, 400, 1000, 450, 120], 2.078_208),
+            (config(-5.0, 0.0), [100, 400, 1000, 450, 120], 4.134_428),
+            (config(0.0, 5.0), [120, 450, 1000, 400, 100], -4.134_428),
+            (config(0.0, 5.0), [20, 300, 900, 350, 30], 2.554_100),
+            (config(0.0, 5.0), [0, 50, 200, 150, 100], 10.032_168),
+        ];
+        for (config, pentanomial, expected) in cases {
+            let llr = sprt_with(config, pentanomial).llr();
+            assert!((llr - expected).abs() < 1e-3, "{:?}: {} != {}", pentanomial, llr, expected);
+        }
+    }
+
+    #[test]
+    fn decisions_use_the_bounds() {
+        assert_eq!(sprt_with(config(0.0, 5.0), [0, 50, 200, 150, 100]).decision(), Some(SprtDecision::AcceptH1));
+        assert_eq!(sprt_with(config(0.0, 5.0), [120, 450, 1000, 400, 100]).decision(), Some(SprtDecision::AcceptH0));
+        assert_eq!(sprt_with(config(0.0, 5.0), [100, 400, 1000, 450, 120]).decision(), None);
+        // A single kind of pair outcome says nothing about the variance yet.
+        assert_eq!(sprt_with(config(0.0, 5.0), [0, 0, 0, 0, 40]).llr(), 0.0);
+    }
+
+    #[test]
+    fn failed_games_drop_their_pair() {
+        let mut sprt = Sprt::new(config(0.0, 5.0));
+        sprt.record(0, 1.0);
+        sprt.discard(1);
+        sprt.discard(2);
+        sprt.record(3, 1.0);
+        sprt.record(4, 0.5);
+        sprt.record(5, 0.5);
+        assert_eq!(sprt.pentanomial(), [0, 0, 1, 0, 0]);
+        assert!(sprt.pending.is_empty() && sprt.dropped.is_empty());
+    }
+
+    #[test]
+    fn parse_rejects_out_of_range_values() {
+        let config = SprtConfig::parse(&params("elo0=-2 elo1=3 alpha=0.1")).unwrap();
+        assert_eq!((config.elo0, config.elo1, config.alpha, config.beta), (-2.0, 3.0, 0.1, 0.05));
+
+        for text in ["alpha=0", "beta=0", "alpha=0.5", "beta=-0.1", "elo0=5 elo1=5", "gamma=1", "alpha"] {
+            assert!(SprtConfig::parse(&params(text)).is_err(), "{} was accepted", text);
+        }
+    }
+}
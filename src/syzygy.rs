@@ -0,0 +1,1193 @@
+use crate::chess::{Board, GameResult, Move, PieceType, Player, Position};
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+const MAX_PIECES: usize = 7;
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// Per-table flags stored ahead of each compressed section.
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/// Win/draw/loss from the side to move's point of view. Cursed wins and
+/// blessed losses are decided by the fifty-move rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_i32(value: i32) -> Wdl {
+        match value {
+            i32::MIN..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    fn negate(self) -> Wdl {
+        Wdl::from_i32(-(self as i32))
+    }
+}
+
+impl fmt::Display for Wdl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            Wdl::Loss => "loss",
+            Wdl::BlessedLoss => "blessed loss",
+            Wdl::Draw => "draw",
+            Wdl::CursedWin => "cursed win",
+            Wdl::Win => "win",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+impl TableKind {
+    fn extension(self) -> &'static str {
+        match self {
+            TableKind::Wdl => "rtbw",
+            TableKind::Dtz => "rtbz",
+        }
+    }
+}
+
+/// A set of Syzygy tables found in one or more directories.
+#[derive(Debug)]
+pub struct Tablebase {
+    directories: Vec<PathBuf>,
+    available: HashSet<String>,
+    max_pieces: usize,
+    tables: Mutex<HashMap<(String, TableKind), Arc<Table>>>,
+}
+
+impl Tablebase {
+    /// Opens every directory in a `SyzygyPath`-style list (`:` separated, `;` on Windows).
+    pub fn open(path_list: &str) -> Result<Self> {
+        let separator = if cfg!(windows) { ';' } else { ':' };
+        let directories: Vec<PathBuf> = path_list
+            .split(separator)
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .collect();
+
+        let mut available = HashSet::new();
+        for directory in &directories {
+            let entries = fs::read_dir(directory)
+                .map_err(|e| anyhow!("Cannot read Syzygy directory '{}': {}", directory.display(), e))?;
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|ext| ext == "rtbw") {
+                    if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                        available.insert(stem.to_string());
+                    }
+                }
+            }
+        }
+
+        let max_pieces = available
+            .iter()
+            .map(|name| name.chars().filter(|c| c.is_ascii_alphabetic() && *c != 'v').count())
+            .max()
+            .unwrap_or(0);
+        if max_pieces == 0 {
+            return Err(anyhow!("No Syzygy tables found in '{}'", path_list));
+        }
+
+        Ok(Tablebase { directories, available, max_pieces, tables: Mutex::new(HashMap::new()) })
+    }
+
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Whether the position is small enough and free of castling rights.
+    pub fn covers(&self, board: &Board) -> bool {
+        let castling = [Player::White, Player::Black]
+            .iter()
+            .any(|&player| board.can_castle(player, true) || board.can_castle(player, false));
+        !castling && piece_squares(board).len() <= self.max_pieces
+    }
+
+    pub fn probe_wdl(&self, board: &Board) -> Result<Wdl> {
+        self.check_covered(board)?;
+        Ok(self.search(board, false)?.0)
+    }
+
+    /// Distance to the next capture or pawn move in plies, signed like the WDL score.
+    /// Values beyond 100 mark cursed wins and blessed losses.
+    pub fn probe_dtz(&self, board: &Board) -> Result<i32> {
+        self.check_covered(board)?;
+        self.dtz(board)
+    }
+
+    /// The game result with perfect play, or `None` if the position can't be probed.
+    /// Cursed wins and blessed losses are draws under the fifty-move rule.
+    pub fn probe_result(&self, board: &Board) -> Option<GameResult> {
+        if !self.covers(board) {
+            return None;
+        }
+        let player = board.current_player();
+        match self.probe_wdl(board).ok()? {
+            Wdl::Win => Some(GameResult::win_for(player)),
+            Wdl::Loss => Some(GameResult::win_for(player.opposite())),
+            _ => Some(GameResult::Draw),
+        }
+    }
+
+    fn check_covered(&self, board: &Board) -> Result<()> {
+        if self.covers(board) {
+            Ok(())
+        } else {
+            Err(anyhow!("Position is not covered by the tablebases"))
+        }
+    }
+
+    fn table(&self, name: &str, kind: TableKind) -> Result<Arc<Table>> {
+        let key = (name.to_string(), kind);
+        let mut tables = self.tables.lock().map_err(|_| anyhow!("Tablebase cache poisoned"))?;
+        if let Some(table) = tables.get(&key) {
+            return Ok(table.clone());
+        }
+
+        let file_name = format!("{}.{}", name, kind.extension());
+        let path = self
+            .directories
+            .iter()
+            .map(|directory| directory.join(&file_name))
+            .find(|path| path.exists())
+            .ok_or_else(|| anyhow!("Missing tablebase file {}", file_name))?;
+        let table = Arc::new(Table::open(path, name, kind)?);
+        tables.insert(key, table.clone());
+        Ok(table)
+    }
+
+    // Captures (and pawn moves, when asked) are resolved by search because the
+    // tables store "don't care" values where a zeroing move is best. Returns the
+    // score and whether the best move is a zeroing one.
+    fn search(&self, board: &Board, pawn_moves: bool) -> Result<(Wdl, bool)> {
+        let moves = board.get_legal_moves();
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+
+        for chess_move in &moves {
+            let zeroing = is_capture(board, chess_move) || (pawn_moves && is_pawn_move(board, chess_move));
+            if !zeroing {
+                continue;
+            }
+            searched += 1;
+
+            let mut next = board.clone();
+            next.make_move(chess_move)?;
+            let value = self.search(&next, false)?.0.negate();
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Ok((value, true));
+                }
+            }
+        }
+
+        let all_searched = searched > 0 && searched == moves.len();
+        let value = if all_searched { best } else { self.probe_table_wdl(board)? };
+
+        if best >= value {
+            Ok((best, best > Wdl::Draw || all_searched))
+        } else {
+            Ok((value, false))
+        }
+    }
+
+    fn dtz(&self, board: &Board) -> Result<i32> {
+        let (wdl, zeroing) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if zeroing {
+            return Ok(dtz_before_zeroing(wdl));
+        }
+
+        let sign = (wdl as i32).signum();
+        if let Some(dtz) = self.probe_table_dtz(board, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Ok((dtz + if cursed { 100 } else { 0 }) * sign);
+        }
+
+        // The table only stores the other side to move: take the best reply.
+        let mut min_dtz = i32::MAX;
+        for chess_move in board.get_legal_moves() {
+            let zeroing = is_zeroing(board, &chess_move);
+            let mut next = board.clone();
+            next.make_move(&chess_move)?;
+
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(&next, false)?.0)
+            } else {
+                -self.dtz(&next)?
+            };
+            if dtz == 1 && next.is_in_check(next.current_player()) && next.get_legal_moves().is_empty() {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == sign {
+                min_dtz = dtz;
+            }
+        }
+
+        Ok(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    fn probe_table_wdl(&self, board: &Board) -> Result<Wdl> {
+        let pieces = piece_squares(board);
+        if pieces.len() == 2 {
+            return Ok(Wdl::Draw);
+        }
+        let lookup = self.lookup(board, &pieces, TableKind::Wdl)?;
+        let value = lookup.table.probe(&pieces, &lookup, Wdl::Draw)?;
+        Ok(Wdl::from_i32(value.unwrap_or(2) - 2))
+    }
+
+    fn probe_table_dtz(&self, board: &Board, wdl: Wdl) -> Result<Option<i32>> {
+        let pieces = piece_squares(board);
+        if pieces.len() == 2 {
+            return Ok(Some(0));
+        }
+        let lookup = self.lookup(board, &pieces, TableKind::Dtz)?;
+        lookup.table.probe(&pieces, &lookup, wdl)
+    }
+
+    // Tables are stored with the stronger side as white; find which way round this position is.
+    fn lookup(&self, board: &Board, pieces: &[(u8, usize)], kind: TableKind) -> Result<Lookup> {
+        let white = material_name(pieces, false);
+        let black = material_name(pieces, true);
+        let black_to_move = board.current_player() == Player::Black;
+
+        let (name, flip) = if white == black {
+            (format!("{}v{}", white, black), black_to_move)
+        } else if self.available.contains(&format!("{}v{}", white, black)) {
+            (format!("{}v{}", white, black), false)
+        } else {
+            (format!("{}v{}", black, white), true)
+        };
+
+        let table = self.table(&name, kind)?;
+        Ok(Lookup { table, flip, stm: usize::from(flip ^ black_to_move) })
+    }
+}
+
+struct Lookup {
+    table: Arc<Table>,
+    flip: bool,
+    stm: usize,
+}
+
+// Piece codes as used inside the files: 1..6 for white pawn..king, +8 for black.
+fn piece_code(piece_type: PieceType, player: Player) -> u8 {
+    let code = match piece_type {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 4,
+        PieceType::Queen => 5,
+        PieceType::King => 6,
+    };
+    if player == Player::White { code } else { code + 8 }
+}
+
+/// All pieces as (code, square index a1=0 .. h8=63), in square order.
+fn piece_squares(board: &Board) -> Vec<(u8, usize)> {
+    Position::all()
+        .filter_map(|position| {
+            let piece = board.piece_at(position)?;
+            Some((piece_code(piece.piece_type, piece.player), position.rank as usize * 8 + position.file as usize))
+        })
+        .collect()
+}
+
+fn material_name(pieces: &[(u8, usize)], black: bool) -> String {
+    let mut name = String::new();
+    for (code, letter) in [(6, 'K'), (5, 'Q'), (4, 'R'), (3, 'B'), (2, 'N'), (1, 'P')] {
+        let code = if black { code + 8 } else { code };
+        for _ in pieces.iter().filter(|(c, _)| *c == code) {
+            name.push(letter);
+        }
+    }
+    name
+}
+
+fn is_capture(board: &Board, chess_move: &Move) -> bool {
+    chess_move.is_en_passant() || board.piece_at(chess_move.to()).is_some()
+}
+
+fn is_pawn_move(board: &Board, chess_move: &Move) -> bool {
+    board.piece_at(chess_move.from()).is_some_and(|piece| piece.piece_type == PieceType::Pawn)
+}
+
+fn is_zeroing(board: &Board, chess_move: &Move) -> bool {
+    is_capture(board, chess_move) || is_pawn_move(board, chess_move)
+}
+
+// DTZ tables don't store scores for zeroing moves, but the DTZ just before one follows from the WDL.
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0,
+    }
+}
+
+fn off_diagonal(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+/// Square and combination tables shared by every table's index calculation.
+struct Indices {
+    map_pawns: [usize; 64],
+    map_b1h1h7: [usize; 64],
+    map_a1d1d4: [usize; 64],
+    map_kk: [[u64; 64]; 10],
+    binomial: [[u64; 64]; MAX_PIECES],
+    lead_pawn_index: [[u64; 64]; MAX_PIECES],
+    lead_pawns_size: [[u64; 4]; MAX_PIECES],
+}
+
+#[allow(clippy::needless_range_loop)]
+fn indices() -> &'static Indices {
+    static INDICES: OnceLock<Indices> = OnceLock::new();
+    INDICES.get_or_init(|| {
+        let mut map_b1h1h7 = [0; 64];
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        // The a1-d1-d4 triangle, with the diagonal squares numbered last.
+        let mut map_a1d1d4 = [0; 64];
+        let mut diagonal = Vec::new();
+        code = 0;
+        for square in 0..=27 {
+            if off_diagonal(square) < 0 && square % 8 <= 3 {
+                map_a1d1d4[square] = code;
+                code += 1;
+            } else if off_diagonal(square) == 0 && square % 8 <= 3 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // The 462 legal placements of two kings with the first in the triangle.
+        let mut map_kk = [[0; 64]; 10];
+        let mut both_on_diagonal = Vec::new();
+        let mut code = 0;
+        for index in 0..10 {
+            for first in 0..=27 {
+                if map_a1d1d4[first] != index || (index == 0 && first != 1) {
+                    continue;
+                }
+                for second in 0..64 {
+                    let file_distance = (first % 8).abs_diff(second % 8);
+                    let rank_distance = (first / 8).abs_diff(second / 8);
+                    if file_distance <= 1 && rank_distance <= 1 {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) > 0 {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((index, second));
+                    } else {
+                        map_kk[index][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (index, second) in both_on_diagonal {
+            map_kk[index][second] = code;
+            code += 1;
+        }
+
+        let mut binomial = [[0; 64]; MAX_PIECES];
+        binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                binomial[k][n] = if k > 0 { binomial[k - 1][n - 1] } else { 0 } + if k < n { binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // Pawn squares a2-h7 numbered so the leading pawn (nearest the edge, then lowest) is highest.
+        let mut map_pawns = [0; 64];
+        let mut lead_pawn_index = [[0; 64]; MAX_PIECES];
+        let mut lead_pawns_size = [[0; 4]; MAX_PIECES];
+        let mut available: usize = 47;
+        for lead_pawns in 1..=5 {
+            for file in 0..4 {
+                let mut index = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        map_pawns[square] = available;
+                        map_pawns[square ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    lead_pawn_index[lead_pawns][square] = index;
+                    index += binomial[lead_pawns - 1][map_pawns[square]];
+                }
+                lead_pawns_size[lead_pawns][file] = index;
+            }
+        }
+
+        Indices { map_pawns, map_b1h1h7, map_a1d1d4, map_kk, binomial, lead_pawn_index, lead_pawns_size }
+    })
+}
+
+/// Compression and indexing data for one side to move and one leading-pawn file.
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    min_sym_len: u8,
+    block_size: u64,
+    span: u64,
+    lowest_sym: Vec<u16>,
+    btree: Vec<[u8; 3]>,
+    symlen: Vec<u32>,
+    base64: Vec<u64>,
+    block_length_size: u64,
+    sparse_index_size: u64,
+    num_blocks: u64,
+    sparse_index_offset: u64,
+    block_length_offset: u64,
+    data_offset: u64,
+    pieces: [u8; MAX_PIECES],
+    group_index: [u64; MAX_PIECES + 1],
+    group_len: [usize; MAX_PIECES + 1],
+    // File offsets of the DTZ value maps for win, loss, cursed win and blessed loss.
+    map_offset: [u64; 4],
+}
+
+impl PairsData {
+    fn left(&self, symbol: usize) -> usize {
+        let node = self.btree[symbol];
+        (((node[1] & 0xF) as usize) << 8) | node[0] as usize
+    }
+
+    fn right(&self, symbol: usize) -> usize {
+        let node = self.btree[symbol];
+        ((node[2] as usize) << 4) | (node[1] >> 4) as usize
+    }
+
+    // Each Recursive Pairing symbol expands into a left and a right child.
+    fn set_symlen(&mut self, symbol: usize, visited: &mut [bool]) -> u32 {
+        visited[symbol] = true;
+        let right = self.right(symbol);
+        if right == 0xFFF {
+            return 0;
+        }
+        let left = self.left(symbol);
+        if !visited[left] {
+            self.symlen[left] = self.set_symlen(left, visited);
+        }
+        if !visited[right] {
+            self.symlen[right] = self.set_symlen(right, visited);
+        }
+        self.symlen[left] + self.symlen[right] + 1
+    }
+}
+
+#[derive(Debug)]
+struct Table {
+    file: Mutex<File>,
+    kind: TableKind,
+    symmetric: bool,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    piece_count: usize,
+    pawn_count: [usize; 2],
+    // Indexed by [side to move][leading pawn file]
+    pairs: Vec<Vec<PairsData>>,
+}
+
+/// Sequential reads from a table file during header parsing.
+struct HeaderReader<'a> {
+    file: &'a mut File,
+    offset: u64,
+}
+
+impl HeaderReader<'_> {
+    fn bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let bytes = read_at(self.file, self.offset, len)?;
+        self.offset += len as u64;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn align(&mut self, alignment: u64) {
+        self.offset = self.offset.div_ceil(alignment) * alignment;
+    }
+}
+
+fn read_at(file: &mut File, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let mut buffer = vec![0; len];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)
+        .map_err(|e| anyhow!("Truncated tablebase file: {}", e))?;
+    Ok(buffer)
+}
+
+impl Table {
+    fn open(path: PathBuf, name: &str, kind: TableKind) -> Result<Self> {
+        let mut file = File::open(&path)?;
+
+        let (white, black) = name.split_once('v').ok_or_else(|| anyhow!("Bad table name {}", name))?;
+        let count = |side: &str, letter: char| side.chars().filter(|&c| c == letter).count();
+        let white_pawns = count(white, 'P');
+        let black_pawns = count(black, 'P');
+        let has_unique_pieces = [white, black]
+            .iter()
+            .any(|side| "QRBNP".chars().any(|letter| count(side, letter) == 1));
+        // The side with fewer (but some) pawns leads, as it compresses better.
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] };
+
+        let mut table = Table {
+            file: Mutex::new(file.try_clone()?),
+            kind,
+            symmetric: white == black,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            piece_count: white.len() + black.len(),
+            pawn_count,
+            pairs: Vec::new(),
+        };
+
+        let mut reader = HeaderReader { file: &mut file, offset: 0 };
+        let magic = match kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+        if reader.bytes(4)? != magic {
+            return Err(anyhow!("'{}' is not a Syzygy {} table", path.display(), kind.extension()));
+        }
+        table.parse(&mut reader)?;
+        Ok(table)
+    }
+
+    fn sides(&self) -> usize {
+        if self.kind == TableKind::Wdl && !self.symmetric { 2 } else { 1 }
+    }
+
+    fn files(&self) -> usize {
+        if self.has_pawns { 4 } else { 1 }
+    }
+
+    fn get(&self, stm: usize, file: usize) -> &PairsData {
+        &self.pairs[stm % self.sides()][if self.has_pawns { file } else { 0 }]
+    }
+
+    fn parse(&mut self, reader: &mut HeaderReader) -> Result<()> {
+        let header = reader.u8()?;
+        if (header & 2 != 0) != self.has_pawns || (header & 1 != 0) == self.symmetric {
+            return Err(anyhow!("Tablebase header does not match its file name"));
+        }
+
+        let sides = self.sides();
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        self.pairs = vec![vec![PairsData::default(); self.files()]; sides];
+
+        for file in 0..self.files() {
+            let order_bytes = reader.bytes(1 + usize::from(both_pawns))?;
+            let order = [
+                [order_bytes[0] & 0xF, if both_pawns { order_bytes[1] & 0xF } else { 0xF }],
+                [order_bytes[0] >> 4, if both_pawns { order_bytes[1] >> 4 } else { 0xF }],
+            ];
+            let piece_bytes = reader.bytes(self.piece_count)?;
+            for (side, &side_order) in order.iter().enumerate().take(sides) {
+                for (k, byte) in piece_bytes.iter().enumerate() {
+                    self.pairs[side][file].pieces[k] = if side == 1 { byte >> 4 } else { byte & 0xF };
+                }
+                self.set_groups(side, file, side_order);
+            }
+        }
+        reader.align(2);
+
+        for file in 0..self.files() {
+            for side in 0..sides {
+                set_sizes(&mut self.pairs[side][file], reader)?;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            for file in 0..self.files() {
+                let flags = self.pairs[0][file].flags;
+                if flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                for i in 0..4 {
+                    if flags & FLAG_WIDE != 0 {
+                        reader.align(2);
+                        self.pairs[0][file].map_offset[i] = reader.offset + 2;
+                        let len = reader.u16()? as u64;
+                        reader.offset += 2 * len;
+                    } else {
+                        self.pairs[0][file].map_offset[i] = reader.offset + 1;
+                        let len = reader.u8()? as u64;
+                        reader.offset += len;
+                    }
+                }
+            }
+            reader.align(2);
+        }
+
+        for file in 0..self.files() {
+            for side in 0..sides {
+                let pairs = &mut self.pairs[side][file];
+                pairs.sparse_index_offset = reader.offset;
+                reader.offset += pairs.sparse_index_size * 6;
+            }
+        }
+        for file in 0..self.files() {
+            for side in 0..sides {
+                let pairs = &mut self.pairs[side][file];
+                pairs.block_length_offset = reader.offset;
+                reader.offset += pairs.block_length_size * 2;
+            }
+        }
+        for file in 0..self.files() {
+            for side in 0..sides {
+                reader.align(64);
+                let pairs = &mut self.pairs[side][file];
+                pairs.data_offset = reader.offset;
+                reader.offset += pairs.num_blocks * pairs.block_size;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Pieces of one type and color form a group; the leading group is the pawns on
+    // the leading side, or the first three unique pieces, or the two kings.
+    fn set_groups(&mut self, side: usize, file: usize, order: [u8; 2]) {
+        let indices = indices();
+        let has_pawns = self.has_pawns;
+        let has_unique_pieces = self.has_unique_pieces;
+        let both_pawns = has_pawns && self.pawn_count[1] > 0;
+        let piece_count = self.piece_count;
+        let pairs = &mut self.pairs[side][file];
+
+        let mut first_len: i32 = if has_pawns { 0 } else if has_unique_pieces { 3 } else { 2 };
+        let mut n = 0;
+        pairs.group_len[0] = 1;
+        for i in 1..piece_count {
+            first_len -= 1;
+            if first_len > 0 || pairs.pieces[i] == pairs.pieces[i - 1] {
+                pairs.group_len[n] += 1;
+            } else {
+                n += 1;
+                pairs.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        pairs.group_len[n] = 0;
+
+        // Groups are combined in a per-table order; remaining pawns are always second.
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - pairs.group_len[0] - if both_pawns { pairs.group_len[1] } else { 0 };
+        let mut index: u64 = 1;
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                pairs.group_index[0] = index;
+                index *= if has_pawns {
+                    indices.lead_pawns_size[pairs.group_len[0]][file]
+                } else if has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                pairs.group_index[1] = index;
+                index *= indices.binomial[pairs.group_len[1]][48 - pairs.group_len[0]];
+            } else {
+                pairs.group_index[next] = index;
+                index *= indices.binomial[pairs.group_len[next]][free_squares];
+                free_squares -= pairs.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        pairs.group_index[n] = index;
+    }
+
+    /// Looks up the stored value; `None` when a DTZ table only holds the other side to move.
+    fn probe(&self, pieces: &[(u8, usize)], lookup: &Lookup, wdl: Wdl) -> Result<Option<i32>> {
+        let (pairs, index) = match self.position_index(pieces, lookup) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+
+        let value = self.decompress(pairs, index)? as i32;
+        match self.kind {
+            TableKind::Wdl => Ok(Some(value)),
+            TableKind::Dtz => self.map_dtz(pairs, value, wdl).map(Some),
+        }
+    }
+
+    // Maps the position onto the table's canonical square ordering and symmetry,
+    // then combines the piece groups into a single index.
+    fn position_index(&self, pieces: &[(u8, usize)], lookup: &Lookup) -> Option<(&PairsData, u64)> {
+        let indices = indices();
+        let flip_color = if lookup.flip { 8 } else { 0 };
+        let flip_squares = if lookup.flip { 56 } else { 0 };
+        let stm = lookup.stm;
+
+        let mut squares: Vec<usize> = Vec::with_capacity(MAX_PIECES);
+        let mut codes: Vec<u8> = Vec::with_capacity(MAX_PIECES);
+        let mut lead_pawns = 0;
+        let mut tb_file = 0;
+
+        if self.has_pawns {
+            let lead_code = self.get(0, 0).pieces[0] ^ flip_color;
+            for &(code, square) in pieces.iter().filter(|(code, _)| *code == lead_code) {
+                squares.push(square ^ flip_squares);
+                codes.push(code ^ flip_color);
+            }
+            lead_pawns = squares.len();
+
+            let lead = (0..lead_pawns).max_by_key(|&i| indices.map_pawns[squares[i]]).unwrap_or(0);
+            squares.swap(0, lead);
+            tb_file = squares[0] % 8;
+            if tb_file > 3 {
+                tb_file = 7 - tb_file;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            let flags = self.get(stm, tb_file).flags;
+            let both_sides_stored = self.symmetric && !self.has_pawns;
+            if usize::from(flags & FLAG_STM) != stm && !both_sides_stored {
+                return None;
+            }
+        }
+
+        let lead_code = if self.has_pawns { Some(self.get(0, 0).pieces[0] ^ flip_color) } else { None };
+        for &(code, square) in pieces {
+            if Some(code) == lead_code {
+                continue;
+            }
+            squares.push(square ^ flip_squares);
+            codes.push(code ^ flip_color);
+        }
+
+        let pairs = self.get(stm, tb_file);
+        let size = squares.len();
+
+        // Reorder the pieces into the table's own sequence.
+        for i in lead_pawns..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if pairs.pieces[i] == codes[j] {
+                    codes.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        if squares[0] % 8 > 3 {
+            for square in squares.iter_mut() {
+                *square ^= 7;
+            }
+        }
+
+        let mut index: u64;
+        if self.has_pawns {
+            index = indices.lead_pawn_index[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&square| indices.map_pawns[square]);
+            for (i, &square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                index += indices.binomial[i][indices.map_pawns[square]];
+            }
+        } else {
+            if squares[0] / 8 > 3 {
+                for square in squares.iter_mut() {
+                    *square ^= 56;
+                }
+            }
+
+            for i in 0..pairs.group_len[0] {
+                if off_diagonal(squares[i]) == 0 {
+                    continue;
+                }
+                if off_diagonal(squares[i]) > 0 {
+                    for square in squares[i..].iter_mut() {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            index = if self.has_unique_pieces {
+                let adjust1 = usize::from(squares[1] > squares[0]);
+                let adjust2 = usize::from(squares[2] > squares[0]) + usize::from(squares[2] > squares[1]);
+                let rank = |square: usize| (square / 8) as u64;
+
+                if off_diagonal(squares[0]) != 0 {
+                    ((indices.map_a1d1d4[squares[0]] * 63 + (squares[1] - adjust1)) * 62 + squares[2] - adjust2) as u64
+                } else if off_diagonal(squares[1]) != 0 {
+                    (6 * 63 + rank(squares[0]) * 28 + indices.map_b1h1h7[squares[1]] as u64) * 62
+                        + (squares[2] - adjust2) as u64
+                } else if off_diagonal(squares[2]) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank(squares[0]) * 7 * 28
+                        + (rank(squares[1]) - adjust1 as u64) * 28
+                        + indices.map_b1h1h7[squares[2]] as u64
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank(squares[0]) * 7 * 6
+                        + (rank(squares[1]) - adjust1 as u64) * 6
+                        + (rank(squares[2]) - adjust2 as u64)
+                }
+            } else {
+                indices.map_kk[indices.map_a1d1d4[squares[0]]][squares[1]]
+            };
+        }
+
+        index *= pairs.group_index[0];
+        let mut group_start = pairs.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while pairs.group_len[next] != 0 {
+            let len = pairs.group_len[next];
+            squares[group_start..group_start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|&&s| square > s).count();
+                let offset = if remaining_pawns { 8 } else { 0 };
+                n += indices.binomial[i + 1][square - adjust - offset];
+            }
+            remaining_pawns = false;
+            index += n * pairs.group_index[next];
+            group_start += len;
+            next += 1;
+        }
+
+        Some((pairs, index))
+    }
+
+    fn map_dtz(&self, pairs: &PairsData, value: i32, wdl: Wdl) -> Result<i32> {
+        let mut value = value;
+        if pairs.flags & FLAG_MAPPED != 0 {
+            let map = match wdl {
+                Wdl::Win | Wdl::Draw => 0,
+                Wdl::Loss => 1,
+                Wdl::CursedWin => 2,
+                Wdl::BlessedLoss => 3,
+            };
+            let mut file = self.lock_file()?;
+            value = if pairs.flags & FLAG_WIDE != 0 {
+                let bytes = read_at(&mut file, pairs.map_offset[map] + 2 * value as u64, 2)?;
+                u16::from_le_bytes([bytes[0], bytes[1]]) as i32
+            } else {
+                read_at(&mut file, pairs.map_offset[map] + value as u64, 1)?[0] as i32
+            };
+        }
+
+        // Stored in moves unless the flags say plies; we always return plies.
+        let in_moves = match wdl {
+            Wdl::Win => pairs.flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => pairs.flags & FLAG_LOSS_PLIES == 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => true,
+            Wdl::Draw => false,
+        };
+        if in_moves {
+            value *= 2;
+        }
+        Ok(value + 1)
+    }
+
+    fn lock_file(&self) -> Result<std::sync::MutexGuard<'_, File>> {
+        self.file.lock().map_err(|_| anyhow!("Tablebase file lock poisoned"))
+    }
+
+    // Finds the block holding `index` via the sparse index, then walks the
+    // canonical Huffman symbols and their pair expansions down to the value.
+    fn decompress(&self, pairs: &PairsData, index: u64) -> Result<u16> {
+        if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+            return Ok(pairs.min_sym_len as u16);
+        }
+        let mut file = self.lock_file()?;
+
+        let k = index / pairs.span;
+        let entry = read_at(&mut file, pairs.sparse_index_offset + 6 * k, 6)?;
+        let mut block = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) as u64;
+        let mut offset = u16::from_le_bytes([entry[4], entry[5]]) as i64;
+        offset += (index % pairs.span) as i64 - (pairs.span / 2) as i64;
+
+        let block_length = |file: &mut File, block: u64| -> Result<i64> {
+            let bytes = read_at(file, pairs.block_length_offset + 2 * block, 2)?;
+            Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as i64)
+        };
+        while offset < 0 {
+            block -= 1;
+            offset += block_length(&mut file, block)? + 1;
+        }
+        loop {
+            let length = block_length(&mut file, block)?;
+            if offset <= length {
+                break;
+            }
+            offset -= length + 1;
+            block += 1;
+        }
+
+        let data = read_at(&mut file, pairs.data_offset + block * pairs.block_size, pairs.block_size as usize)?;
+        drop(file);
+        let word = |position: usize| -> u64 {
+            let mut bytes = [0; 4];
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = data.get(position + i).copied().unwrap_or(0);
+            }
+            u32::from_be_bytes(bytes) as u64
+        };
+
+        let mut buffer = (word(0) << 32) | word(4);
+        let mut position = 8;
+        let mut buffer_bits = 64;
+        let min_sym_len = pairs.min_sym_len as usize;
+
+        let mut symbol;
+        loop {
+            let mut len = 0;
+            while buffer < pairs.base64[len] {
+                len += 1;
+            }
+            symbol = ((buffer - pairs.base64[len]) >> (64 - len - min_sym_len)) as usize;
+            symbol += pairs.lowest_sym[len] as usize;
+
+            if offset < pairs.symlen[symbol] as i64 + 1 {
+                break;
+            }
+            offset -= pairs.symlen[symbol] as i64 + 1;
+            let bits = len + min_sym_len;
+            buffer <<= bits;
+            buffer_bits -= bits;
+            if buffer_bits <= 32 {
+                buffer_bits += 32;
+                buffer |= word(position) << (64 - buffer_bits);
+                position += 4;
+            }
+        }
+
+        while pairs.symlen[symbol] != 0 {
+            let left = pairs.left(symbol);
+            if offset < pairs.symlen[left] as i64 + 1 {
+                symbol = left;
+            } else {
+                offset -= pairs.symlen[left] as i64 + 1;
+                symbol = pairs.right(symbol);
+            }
+        }
+
+        Ok(pairs.left(symbol) as u16)
+    }
+}
+
+fn set_sizes(pairs: &mut PairsData, reader: &mut HeaderReader) -> Result<()> {
+    pairs.flags = reader.u8()?;
+    if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+        pairs.min_sym_len = reader.u8()?;
+        return Ok(());
+    }
+
+    let groups = pairs.group_len.iter().position(|&len| len == 0).unwrap_or(MAX_PIECES);
+    let table_size = pairs.group_index[groups];
+
+    pairs.block_size = 1 << reader.u8()?;
+    pairs.span = 1 << reader.u8()?;
+    pairs.sparse_index_size = table_size.div_ceil(pairs.span);
+    let padding = reader.u8()? as u64;
+    pairs.num_blocks = reader.u32()? as u64;
+    pairs.block_length_size = pairs.num_blocks + padding;
+
+    let max_sym_len = reader.u8()?;
+    pairs.min_sym_len = reader.u8()?;
+    let lengths = (max_sym_len - pairs.min_sym_len) as usize + 1;
+    pairs.lowest_sym = (0..lengths).map(|_| reader.u16()).collect::<Result<_>>()?;
+
+    // Canonical Huffman: longer codes have lower values, so base64 decreases with length.
+    pairs.base64 = vec![0; lengths];
+    for i in (0..lengths - 1).rev() {
+        pairs.base64[i] = (pairs.base64[i + 1] + pairs.lowest_sym[i] as u64 - pairs.lowest_sym[i + 1] as u64) / 2;
+    }
+    for (i, base) in pairs.base64.iter_mut().enumerate() {
+        *base <<= 64 - i - pairs.min_sym_len as usize;
+    }
+
+    let symbols = reader.u16()? as usize;
+    let tree = reader.bytes(3 * symbols)?;
+    pairs.btree = tree.chunks_exact(3).map(|node| [node[0], node[1], node[2]]).collect();
+    pairs.symlen = vec![0; symbols];
+    let mut visited = vec![false; symbols];
+    for symbol in 0..symbols {
+        if !visited[symbol] {
+            pairs.symlen[symbol] = pairs.set_symlen(symbol, &mut visited);
+        }
+    }
+    reader.offset += (symbols & 1) as u64;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // KQvK, KRvK, KPvK and the drawn KBvK and KNvK tables built by tests/syzygy/generate.py.
+    fn tablebase() -> Tablebase {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy");
+        Tablebase::open(path).unwrap()
+    }
+
+    fn probe(tablebase: &Tablebase, fen: &str) -> (Wdl, i32) {
+        let board = Board::from_fen(fen).unwrap();
+        (tablebase.probe_wdl(&board).unwrap(), tablebase.probe_dtz(&board).unwrap())
+    }
+
+    #[test]
+    fn opens_the_fixture_tables() {
+        let tablebase = tablebase();
+        assert_eq!(tablebase.max_pieces(), 3);
+        assert!(tablebase.covers(&Board::from_fen("8/8/8/8/8/8/8/KQ5k w - - 0 1").unwrap()));
+        assert!(!tablebase.covers(&Board::new()));
+    }
+
+    #[test]
+    fn mate_in_one() {
+        let tablebase = tablebase();
+        assert_eq!(probe(&tablebase, "k7/8/1K6/8/8/8/7Q/8 w - - 0 1"), (Wdl::Win, 1));
+        assert_eq!(probe(&tablebase, "k7/8/1K6/8/8/8/8/7R w - - 0 1"), (Wdl::Win, 1));
+        // The same mate with the colours swapped probes the table from Black's side.
+        assert_eq!(probe(&tablebase, "8/7q/8/8/8/1k6/8/K7 b - - 0 1"), (Wdl::Win, 1));
+    }
+
+    #[test]
+    fn mated_stalemated_and_capturing_defender() {
+        let tablebase = tablebase();
+        assert_eq!(probe(&tablebase, "k7/1Q6/1K6/8/8/8/8/8 b - - 0 1"), (Wdl::Loss, -1));
+        assert_eq!(probe(&tablebase, "k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), (Wdl::Draw, 0));
+        assert_eq!(probe(&tablebase, "kQ6/8/1K6/8/8/8/8/8 b - - 0 1"), (Wdl::Draw, 0));
+        assert_eq!(probe(&tablebase, "k7/1R6/8/8/8/8/8/K7 b - - 0 1"), (Wdl::Draw, 0));
+    }
+
+    #[test]
+    fn longest_wins() {
+        let tablebase = tablebase();
+        assert_eq!(probe(&tablebase, "7K/6Q1/8/8/2k5/8/8/8 w - - 0 1"), (Wdl::Win, 19));
+        assert_eq!(probe(&tablebase, "7K/6Q1/8/3k4/8/8/8/8 b - - 0 1"), (Wdl::Loss, -20));
+        assert_eq!(probe(&tablebase, "7K/6R1/5k2/8/8/8/8/8 w - - 0 1"), (Wdl::Win, 31));
+        assert_eq!(probe(&tablebase, "7K/5kR1/8/8/8/8/8/8 b - - 0 1"), (Wdl::Loss, -32));
+    }
+
+    // Textbook king and pawn endings, so the pawn tables are checked against the chess and not
+    // only against the generator: the leading pawn's file, the e-h mirror and the colour flip.
+    #[test]
+    fn king_and_pawn_endings() {
+        let tablebase = tablebase();
+        // King on the sixth rank in front of its pawn wins whoever is to move; the pawn can
+        // only advance after the king steps aside.
+        assert_eq!(probe(&tablebase, "3k4/8/3K4/3P4/8/8/8/8 w - - 0 1"), (Wdl::Win, 3));
+        assert_eq!(probe(&tablebase, "4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), (Wdl::Win, 3));
+        assert_eq!(probe(&tablebase, "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), (Wdl::Loss, -4));
+        // One square ahead of the pawn, the opposition decides.
+        assert_eq!(probe(&tablebase, "8/4k3/8/4K3/4P3/8/8/8 w - - 0 1").0, Wdl::Draw);
+        assert_eq!(probe(&tablebase, "8/4k3/8/4K3/4P3/8/8/8 b - - 0 1").0, Wdl::Loss);
+        // The rook's pawn is a draw with the defender in the corner.
+        assert_eq!(probe(&tablebase, "k7/8/K7/P7/8/8/8/8 w - - 0 1"), (Wdl::Draw, 0));
+        assert_eq!(probe(&tablebase, "7k/8/7K/7P/8/8/8/8 b - - 0 1"), (Wdl::Draw, 0));
+        // An unprotected pawn is simply taken.
+        assert_eq!(probe(&tablebase, "8/8/8/8/8/8/3kP3/7K b - - 0 1"), (Wdl::Draw, 0));
+    }
+
+    #[test]
+    fn pawn_endings_with_black_pawns() {
+        let tablebase = tablebase();
+        assert_eq!(probe(&tablebase, "8/8/8/8/4p3/4k3/8/4K3 b - - 0 1"), (Wdl::Win, 3));
+        assert_eq!(probe(&tablebase, "8/8/8/8/4p3/4k3/8/4K3 w - - 0 1"), (Wdl::Loss, -4));
+        assert_eq!(probe(&tablebase, "8/8/8/4p3/4k3/8/4K3/8 b - - 0 1").0, Wdl::Draw);
+        assert_eq!(probe(&tablebase, "8/8/8/4p3/4k3/8/4K3/8 w - - 0 1").0, Wdl::Loss);
+        assert_eq!(probe(&tablebase, "8/8/8/8/8/k7/p7/K7 b - - 0 1"), (Wdl::Draw, 0));
+    }
+
+    #[test]
+    fn promotions() {
+        let tablebase = tablebase();
+        assert_eq!(probe(&tablebase, "8/4P3/8/8/8/8/k7/4K3 w - - 0 1"), (Wdl::Win, 1));
+        // Promoting mates at once.
+        assert_eq!(probe(&tablebase, "k7/2P5/1K6/8/8/8/8/8 w - - 0 1"), (Wdl::Win, 1));
+        // Queening stalemates, so only the rook wins.
+        assert_eq!(probe(&tablebase, "8/k1P5/8/2K5/8/8/8/8 w - - 0 1"), (Wdl::Win, 1));
+        let board = Board::from_fen("8/k1P5/8/2K5/8/8/8/8 w - - 0 1").unwrap();
+        let mut queened = board.clone();
+        queened.make_move(&board.parse_san("c8=Q").unwrap()).unwrap();
+        assert_eq!(tablebase.probe_wdl(&queened).unwrap(), Wdl::Draw);
+        // A minor piece can't win, and the tables say so.
+        assert_eq!(probe(&tablebase, "8/8/8/8/8/8/8/KB5k w - - 0 1"), (Wdl::Draw, 0));
+        assert_eq!(probe(&tablebase, "8/8/8/8/8/8/8/KN5k b - - 0 1"), (Wdl::Draw, 0));
+    }
+
+    #[test]
+    fn results_follow_the_side_to_move() {
+        let tablebase = tablebase();
+        let won = Board::from_fen("8/8/8/4k3/8/8/8/KR6 b - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_result(&won), Some(GameResult::WhiteWins));
+        let lost = Board::from_fen("8/8/8/4K3/8/8/8/kr6 w - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_result(&lost), Some(GameResult::BlackWins));
+        assert_eq!(tablebase.probe_result(&Board::new()), None);
+    }
+}
@@ -0,0 +1,452 @@
+use crate::chess::{Piece, PieceType, Player};
+use crate::ui::BoardSize;
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use crossterm::style::Color;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ThemeArgs {
+    /// Board colors: brown, green, blue, gray or a theme from theme.toml [default: brown]
+    #[arg(long)]
+    pub theme: Option<String>,
+
+    /// Piece glyphs [default: outline]
+    #[arg(long, value_enum)]
+    pub pieces: Option<PieceSet>,
+
+    /// Colors the terminal can show [default: detected]
+    #[arg(long, value_enum)]
+    pub colors: Option<ColorMode>,
+
+    /// Board size [default: the largest that fits the terminal]
+    #[arg(long, value_enum)]
+    pub board_size: Option<BoardSize>,
+
+    /// Hide the evaluation bar beside the board; the `eval` command toggles it
+    #[arg(long)]
+    pub no_eval_bar: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PieceSet {
+    /// Outlined Unicode pieces for White, filled for Black
+    Outline,
+    /// Filled Unicode pieces in each side's color
+    Filled,
+    /// Letters as in FEN, for fonts without chess glyphs
+    Ascii,
+    /// Chess icons from a Nerd Font
+    NerdFont,
+}
+
+impl PieceSet {
+    pub fn glyph(self, piece: &Piece) -> char {
+        match self {
+            PieceSet::Outline => piece.unicode_symbol(),
+            PieceSet::Filled => Piece::new(piece.piece_type, Player::Black).unicode_symbol(),
+            PieceSet::Ascii => piece.fen_char(),
+            PieceSet::NerdFont => match piece.piece_type {
+                PieceType::King => '\u{f43f}',
+                PieceType::Queen => '\u{f445}',
+                PieceType::Rook => '\u{f447}',
+                PieceType::Bishop => '\u{f43a}',
+                PieceType::Knight => '\u{f441}',
+                PieceType::Pawn => '\u{f443}',
+            },
+        }
+    }
+
+    /// Whether the glyphs look the same for both sides, so only their color tells them apart.
+    pub fn colored_by_side(self) -> bool {
+        matches!(self, PieceSet::Filled | PieceSet::NerdFont)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// 24-bit colors
+    Truecolor,
+    /// The xterm 256-color palette
+    #[value(name = "256")]
+    Ansi256,
+    /// The 16 basic ANSI colors
+    #[value(name = "16")]
+    Ansi16,
+}
+
+impl ColorMode {
+    /// Guesses from `COLORTERM` and `TERM`, as most terminals advertise truecolor there.
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" || env::var_os("WT_SESSION").is_some() {
+            ColorMode::Truecolor
+        } else if term.contains("256") {
+            ColorMode::Ansi256
+        } else {
+            ColorMode::Ansi16
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Parses `#rrggbb`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let hex = text.strip_prefix('#').filter(|hex| hex.len() == 6 && hex.is_ascii());
+        let hex = hex.ok_or_else(|| anyhow!("'{}' is not a #rrggbb color", text))?;
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| anyhow!("'{}' is not a #rrggbb color", text));
+        Ok(Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    /// The nearest entry of the 256-color palette's color cube or gray ramp.
+    fn ansi256(self) -> u8 {
+        const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+        let level = |v: u8| LEVELS.iter().enumerate().min_by_key(|(_, &l)| l.abs_diff(v)).map_or(0, |(i, _)| i);
+        let (r, g, b) = (level(self.0), level(self.1), level(self.2));
+        let cube = Rgb(LEVELS[r], LEVELS[g], LEVELS[b]);
+
+        let average = (self.0 as u16 + self.1 as u16 + self.2 as u16) / 3;
+        let gray_step = (average.saturating_sub(3) / 10).min(23) as u8;
+        let gray_value = 8 + 10 * gray_step;
+        let gray = Rgb(gray_value, gray_value, gray_value);
+
+        if self.distance(gray) < self.distance(cube) {
+            232 + gray_step
+        } else {
+            16 + 36 * r as u8 + 6 * g as u8 + b as u8
+        }
+    }
+
+    fn distance(self, other: Rgb) -> u32 {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+        d(self.0, other.0) + d(self.1, other.1) + d(self.2, other.2)
+    }
+}
+
+/// Colors of each part of the board; square colors are (light, dark) pairs.
+#[derive(Debug, Clone, Copy)]
+pub struct Colors<C> {
+    pub squares: (C, C),
+    pub last_move: (C, C),
+    pub best_move: (C, C),
+    pub check: (C, C),
+    pub selected: (C, C),
+    pub target: C,
+    pub white_piece: C,
+    pub black_piece: C,
+}
+
+pub type Theme = Colors<Rgb>;
+/// A theme converted to what the terminal can show.
+pub type Palette = Colors<Color>;
+
+impl<C: Copy> Colors<C> {
+    fn map<D>(&self, f: impl Fn(C) -> D) -> Colors<D> {
+        let pair = |(light, dark): (C, C)| (f(light), f(dark));
+        Colors {
+            squares: pair(self.squares),
+            last_move: pair(self.last_move),
+            best_move: pair(self.best_move),
+            check: pair(self.check),
+            selected: pair(self.selected),
+            target: f(self.target),
+            white_piece: f(self.white_piece),
+            black_piece: f(self.black_piece),
+        }
+    }
+}
+
+impl Theme {
+    /// Basic terminals ignore the theme: its shades would all collapse onto the same few colors.
+    pub fn palette(&self, mode: ColorMode) -> Palette {
+        match mode {
+            ColorMode::Truecolor => self.map(|Rgb(r, g, b)| Color::Rgb { r, g, b }),
+            ColorMode::Ansi256 => self.map(|rgb| Color::AnsiValue(rgb.ansi256())),
+            ColorMode::Ansi16 => ANSI16,
+        }
+    }
+}
+
+const ANSI16: Palette = Colors {
+    squares: (Color::Grey, Color::DarkYellow),
+    last_move: (Color::Yellow, Color::DarkGreen),
+    best_move: (Color::Cyan, Color::DarkBlue),
+    check: (Color::Red, Color::DarkRed),
+    selected: (Color::Green, Color::DarkCyan),
+    target: Color::DarkGreen,
+    white_piece: Color::White,
+    black_piece: Color::Black,
+};
+
+const BROWN: Theme = Colors {
+    squares: (Rgb(240, 217, 181), Rgb(181, 136, 99)),
+    last_move: (Rgb(205, 210, 106), Rgb(170, 162, 58)),
+    best_move: (Rgb(155, 199, 230), Rgb(100, 150, 200)),
+    check: (Rgb(235, 97, 80), Rgb(204, 51, 41)),
+    selected: (Rgb(130, 200, 190), Rgb(80, 160, 150)),
+    target: Rgb(30, 100, 30),
+    white_piece: Rgb(255, 255, 255),
+    black_piece: Rgb(0, 0, 0),
+};
+
+const BUILT_IN: [(&str, Theme); 4] = [
+    ("brown", BROWN),
+    ("green", Colors { squares: (Rgb(238, 238, 210), Rgb(118, 150, 86)), target: Rgb(20, 70, 20), ..BROWN }),
+    (
+        "blue",
+        Colors {
+            squares: (Rgb(222, 227, 230), Rgb(140, 162, 173)),
+            best_move: (Rgb(150, 210, 170), Rgb(90, 160, 120)),
+            ..BROWN
+        },
+    ),
+    (
+        "gray",
+        Colors {
+            squares: (Rgb(200, 200, 200), Rgb(130, 130, 130)),
+            last_move: (Rgb(220, 210, 120), Rgb(170, 160, 80)),
+            ..BROWN
+        },
+    ),
+];
+
+/// How the board is drawn: theme, glyphs, color depth, size and whether the eval bar is shown.
+#[derive(Debug, Clone, Copy)]
+pub struct Appearance {
+    pub palette: Palette,
+    pub pieces: PieceSet,
+    /// Chosen to fit the terminal when not set.
+    pub board_size: Option<BoardSize>,
+    pub eval_bar: bool,
+}
+
+/// User settings and themes, read from `<config dir>/chess-cli/theme.toml`.
+pub fn config_path() -> Result<PathBuf> {
+    dirs::config_dir()
+        .map(|dir| dir.join("chess-cli").join("theme.toml"))
+        .ok_or_else(|| anyhow!("No config directory found for this user"))
+}
+
+impl ThemeArgs {
+    /// Command-line choices win over the defaults in theme.toml.
+    pub fn appearance(&self) -> Result<Appearance> {
+        let file = match config_path() {
+            Ok(path) if path.exists() => {
+                let text = fs::read_to_string(&path).map_err(|e| anyhow!("Failed to read '{}': {}", path.display(), e))?;
+                ThemeFile::parse(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))?
+            }
+            _ => ThemeFile::default(),
+        };
+
+        let name = self.theme.as_deref().or(file.theme.as_deref()).unwrap_or("brown");
+        let theme = file.find(name).ok_or_else(|| {
+            let mut names: Vec<&str> = BUILT_IN.iter().map(|(name, _)| *name).collect();
+            names.extend(file.themes.iter().map(|(name, _)| name.as_str()));
+            anyhow!("Unknown theme '{}' (available: {})", name, names.join(", "))
+        })?;
+        let pieces = match (self.pieces, &file.pieces) {
+            (Some(pieces), _) => pieces,
+            (None, Some(name)) => PieceSet::from_str(name, true).map_err(|_| anyhow!("Unknown piece set '{}'", name))?,
+            (None, None) => PieceSet::Outline,
+        };
+        let colors = match (self.colors, &file.colors) {
+            (Some(colors), _) => colors,
+            (None, Some(name)) => ColorMode::from_str(name, true).map_err(|_| anyhow!("Unknown color mode '{}'", name))?,
+            (None, None) => ColorMode::detect(),
+        };
+        let board_size = match (self.board_size, &file.board_size) {
+            (Some(size), _) => Some(size),
+            (None, Some(name)) => Some(BoardSize::from_str(name, true).map_err(|_| anyhow!("Unknown board size '{}'", name))?),
+            (None, None) => None,
+        };
+
+        Ok(Appearance { palette: theme.palette(colors), pieces, board_size, eval_bar: !self.no_eval_bar })
+    }
+}
+
+/// The contents of theme.toml: optional `theme`, `pieces`, `colors` and `board_size` defaults, then one table per custom theme.
+#[derive(Debug, Default)]
+struct ThemeFile {
+    theme: Option<String>,
+    pieces: Option<String>,
+    colors: Option<String>,
+    board_size: Option<String>,
+    themes: Vec<(String, Theme)>,
+}
+
+/// theme.toml as written, before the custom themes are applied to their bases.
+#[derive(Debug, Deserialize)]
+struct Settings {
+    theme: Option<String>,
+    pieces: Option<String>,
+    colors: Option<String>,
+    board_size: Option<String>,
+    #[serde(flatten)]
+    themes: BTreeMap<String, ThemeTable>,
+}
+
+/// A custom theme: `base` (brown by default) with some colors replaced. Square keys take a color
+/// or a [light, dark] pair.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeTable {
+    base: Option<String>,
+    light: Option<Rgb>,
+    dark: Option<Rgb>,
+    squares: Option<Shades>,
+    last_move: Option<Shades>,
+    best_move: Option<Shades>,
+    check: Option<Shades>,
+    selected: Option<Shades>,
+    target: Option<Rgb>,
+    white_piece: Option<Rgb>,
+    black_piece: Option<Rgb>,
+}
+
+/// A light and a dark shade, written as one color for both or a [light, dark] pair.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "ShadesText")]
+struct Shades(Rgb, Rgb);
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ShadesText {
+    Both(String),
+    Pair(Vec<String>),
+}
+
+impl TryFrom<ShadesText> for Shades {
+    type Error = anyhow::Error;
+
+    fn try_from(text: ShadesText) -> Result<Self> {
+        match text {
+            ShadesText::Both(color) => Rgb::parse(&color).map(|rgb| Shades(rgb, rgb)),
+            ShadesText::Pair(colors) if colors.len() == 2 => Ok(Shades(Rgb::parse(&colors[0])?, Rgb::parse(&colors[1])?)),
+            ShadesText::Pair(_) => Err(anyhow!("expected a color or a [light, dark] pair")),
+        }
+    }
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        Rgb::parse(&text)
+    }
+}
+
+impl ThemeFile {
+    fn parse(text: &str) -> Result<Self> {
+        let settings: Settings = toml::from_str(text)?;
+        let mut themes = Vec::new();
+        for name in settings.themes.keys() {
+            let theme = resolve(&settings.themes, name, &mut Vec::new())?;
+            themes.push((name.clone(), theme));
+        }
+        Ok(ThemeFile {
+            theme: settings.theme,
+            pieces: settings.pieces,
+            colors: settings.colors,
+            board_size: settings.board_size,
+            themes,
+        })
+    }
+
+    fn find(&self, name: &str) -> Option<Theme> {
+        let user = self.themes.iter().find(|(theme, _)| theme == name).map(|(_, theme)| *theme);
+        user.or_else(|| built_in(name))
+    }
+}
+
+fn built_in(name: &str) -> Option<Theme> {
+    BUILT_IN.iter().find(|(theme, _)| *theme == name).map(|(_, theme)| *theme)
+}
+
+/// The custom theme `name` applied to its base. A theme based on its own name extends the
+/// built-in theme it replaces.
+fn resolve(tables: &BTreeMap<String, ThemeTable>, name: &str, chain: &mut Vec<String>) -> Result<Theme> {
+    let table = &tables[name];
+    chain.push(name.to_string());
+    let base = match table.base.as_deref() {
+        None => BROWN,
+        Some(base) if base == name => built_in(base).ok_or_else(|| anyhow!("[{}]: unknown base theme '{}'", name, base))?,
+        Some(base) if chain.iter().any(|seen| seen == base) => {
+            return Err(anyhow!("[{}]: base themes form a loop: {} -> {}", name, chain.join(" -> "), base))
+        }
+        Some(base) if tables.contains_key(base) => resolve(tables, base, chain)?,
+        Some(base) => built_in(base).ok_or_else(|| anyhow!("[{}]: unknown base theme '{}'", name, base))?,
+    };
+
+    let mut theme = base;
+    let set = |slot: &mut (Rgb, Rgb), shades: Option<Shades>| {
+        if let Some(shades) = shades {
+            *slot = (shades.0, shades.1);
+        }
+    };
+    set(&mut theme.squares, table.squares);
+    theme.squares.0 = table.light.unwrap_or(theme.squares.0);
+    theme.squares.1 = table.dark.unwrap_or(theme.squares.1);
+    set(&mut theme.last_move, table.last_move);
+    set(&mut theme.best_move, table.best_move);
+    set(&mut theme.check, table.check);
+    set(&mut theme.selected, table.selected);
+    theme.target = table.target.unwrap_or(theme.target);
+    theme.white_piece = table.white_piece.unwrap_or(theme.white_piece);
+    theme.black_piece = table.black_piece.unwrap_or(theme.black_piece);
+    Ok(theme)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_the_sample_theme_file() {
+        let file = ThemeFile::parse(include_str!("../tests/theme.toml")).unwrap();
+        assert_eq!(file.theme.as_deref(), Some("midnight"));
+        assert_eq!(file.pieces.as_deref(), Some("filled"));
+        assert_eq!(file.colors.as_deref(), Some("256"));
+        assert_eq!(file.board_size.as_deref(), Some("large"));
+
+        let blue = built_in("blue").unwrap();
+        let midnight = file.find("midnight").unwrap();
+        assert_eq!(midnight.squares, (Rgb(0x9f, 0xa8, 0xb4), Rgb(0x4b, 0x58, 0x68)));
+        assert_eq!(midnight.check, (Rgb(0xff, 0x60, 0x60), Rgb(0xc0, 0x30, 0x30)));
+        assert_eq!(midnight.target, Rgb(0x10, 0x20, 0x30));
+        assert_eq!(midnight.best_move, blue.best_move);
+
+        let dusk = file.find("dusk").unwrap();
+        assert_eq!(dusk.squares, midnight.squares);
+        assert_eq!(dusk.selected, (Rgb(0x33, 0x66, 0x99), Rgb(0x33, 0x66, 0x99)));
+
+        let green = file.find("green").unwrap();
+        assert_eq!(green.squares, (Rgb(238, 238, 210), Rgb(0x5a, 0x7a, 0x40)));
+        assert_eq!(file.find("brown").unwrap().squares, BROWN.squares);
+        assert!(file.find("missing").is_none());
+    }
+
+    #[test]
+    fn rejects_bad_theme_files() {
+        for text in [
+            "pieces = [\"filled\"]",
+            "colour = \"256\"",
+            "[mine]\nlight = \"#12345\"",
+            "[mine]\nshade = \"#123456\"",
+            "[mine]\ncheck = [\"#123456\"]",
+            "[mine]\nbase = \"plaid\"",
+            "[a]\nbase = \"b\"\n[b]\nbase = \"a\"",
+            "theme = \"brown",
+        ] {
+            assert!(ThemeFile::parse(text).is_err(), "accepted {:?}", text);
+        }
+    }
+}
@@ -0,0 +1,434 @@
+use crate::chess::{GameResult, Move};
+use crate::match_runner::{self, Adjudication, GameArgs, GameSettings, Opening, Pairing};
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[derive(clap::Args)]
+pub struct TournamentArgs {
+    /// Participating engine, given once per engine: cmd=PATH[,name=NAME][,option.NAME=VALUE...]
+    #[arg(long = "engine", required = true)]
+    pub engines: Vec<String>,
+
+    #[arg(long, value_enum, default_value = "round-robin")]
+    pub format: TournamentFormat,
+
+    /// Games per pairing with colors alternating, twice over in a double round-robin [default: 1, 2 for gauntlet]
+    #[arg(long)]
+    pub games_per_encounter: Option<u32>,
+
+    #[command(flatten)]
+    pub game: GameArgs,
+
+    /// Progress file; an existing one for the same tournament is resumed
+    #[arg(long, default_value = "tournament.json")]
+    pub state: PathBuf,
+
+    /// Append every finished game to this PGN file [default: the --state file with a .pgn extension]
+    #[arg(long)]
+    pub pgn_out: Option<PathBuf>,
+
+    #[arg(long, default_value = "chess-cli tournament")]
+    pub event: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum TournamentFormat {
+    RoundRobin,
+    DoubleRoundRobin,
+    /// The first engine plays every other engine
+    Gauntlet,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScheduledGame {
+    round: u32,
+    white: usize,
+    black: usize,
+    opening_fen: String,
+    opening_moves: Vec<String>,
+    result: Option<String>,
+    reason: Option<String>,
+}
+
+impl ScheduledGame {
+    fn result(&self) -> Option<GameResult> {
+        match self.result.as_deref() {
+            Some("1-0") => Some(GameResult::WhiteWins),
+            Some("0-1") => Some(GameResult::BlackWins),
+            Some("1/2-1/2") => Some(GameResult::Draw),
+            _ => None,
+        }
+    }
+
+    fn opening(&self) -> Result<Opening> {
+        Ok(Opening {
+            fen: self.opening_fen.clone(),
+            moves: self
+                .opening_moves
+                .iter()
+                .map(|m| Move::from_uci(m))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+/// What a tournament was started with; resuming it with anything else is refused.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TournamentSettings {
+    engines: Vec<String>,
+    format: TournamentFormat,
+    games_per_encounter: u32,
+    time_control: String,
+    openings: Option<PathBuf>,
+    opening_plies: usize,
+    #[serde(default)]
+    adjudication: Adjudication,
+    #[serde(default)]
+    syzygy_path: Option<String>,
+}
+
+/// Everything needed to resume: the schedule is stored with its openings and results.
+#[derive(Debug, Serialize, Deserialize)]
+struct TournamentState {
+    settings: TournamentSettings,
+    names: Vec<String>,
+    games: Vec<ScheduledGame>,
+}
+
+impl TournamentState {
+    fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|e| anyhow!("Invalid tournament state '{}': {}", path.display(), e))
+    }
+
+    // Write-then-rename so an interruption never leaves a truncated file behind.
+    fn save(&self, path: &Path) -> Result<()> {
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    fn finished_games(&self) -> usize {
+        self.games.iter().filter(|game| game.result.is_some()).count()
+    }
+}
+
+/// Pairings per round via the circle method; odd fields get a bye.
+fn round_robin_rounds(players: usize) -> Vec<Vec<(usize, usize)>> {
+    let mut seats: Vec<Option<usize>> = (0..players).map(Some).collect();
+    if seats.len() % 2 == 1 {
+        seats.push(None);
+    }
+    let seat_count = seats.len();
+
+    let mut rounds = Vec::new();
+    for round in 0..seat_count - 1 {
+        let mut pairs = Vec::new();
+        for i in 0..seat_count / 2 {
+            if let (Some(a), Some(b)) = (seats[i], seats[seat_count - 1 - i]) {
+                // The fixed seat alternates colors; everyone else does through rotation.
+                if i == 0 && round % 2 == 1 {
+                    pairs.push((b, a));
+                } else {
+                    pairs.push((a, b));
+                }
+            }
+        }
+        rounds.push(pairs);
+
+        let last = seats.pop().flatten();
+        seats.insert(1, last);
+    }
+    rounds
+}
+
+fn build_schedule(
+    format: TournamentFormat,
+    players: usize,
+    games_per_encounter: u32,
+    openings: &[Opening],
+) -> Vec<ScheduledGame> {
+    let mut games = Vec::new();
+    let mut push = |round: u32, white: usize, black: usize, opening: &Opening| {
+        games.push(ScheduledGame {
+            round,
+            white,
+            black,
+            opening_fen: opening.fen.clone(),
+            opening_moves: opening.moves.iter().map(|m| m.to_uci()).collect(),
+            result: None,
+            reason: None,
+        });
+    };
+
+    match format {
+        TournamentFormat::RoundRobin | TournamentFormat::DoubleRoundRobin => {
+            let rounds = round_robin_rounds(players);
+            // The second leg of a double round-robin repeats the first with colors reversed.
+            let legs = if format == TournamentFormat::DoubleRoundRobin { 2 } else { 1 };
+            let mut round_number = 0;
+            for cycle in 0..(games_per_encounter * legs) as usize {
+                for (index, pairs) in rounds.iter().enumerate() {
+                    round_number += 1;
+                    // Cycles 2k and 2k+1 replay a round's opening with colors reversed.
+                    let opening = &openings[((cycle / 2) * rounds.len() + index) % openings.len()];
+                    for &(a, b) in pairs {
+                        let (white, black) = if cycle % 2 == 0 { (a, b) } else { (b, a) };
+                        push(round_number, white, black, opening);
+                    }
+                }
+            }
+        }
+        TournamentFormat::Gauntlet => {
+            for game in 0..games_per_encounter as usize {
+                let opening = &openings[(game / 2) % openings.len()];
+                for opponent in 1..players {
+                    let (white, black) = if game % 2 == 0 { (0, opponent) } else { (opponent, 0) };
+                    push(game as u32 + 1, white, black, opening);
+                }
+            }
+        }
+    }
+
+    games
+}
+
+#[derive(Debug, Clone, Default)]
+struct Standing {
+    points: f64,
+    games: u32,
+    wins: u32,
+    draws: u32,
+    losses: u32,
+    sonneborn_berger: f64,
+    buchholz: f64,
+}
+
+fn compute_standings(state: &TournamentState) -> Vec<Standing> {
+    let mut standings = vec![Standing::default(); state.names.len()];
+    let scored: Vec<(usize, usize, f64)> = state
+        .games
+        .iter()
+        .filter_map(|game| {
+            let white_score = match game.result()? {
+                GameResult::WhiteWins => 1.0,
+                GameResult::BlackWins => 0.0,
+                GameResult::Draw => 0.5,
+            };
+            Some((game.white, game.black, white_score))
+        })
+        .collect();
+
+    for &(white, black, white_score) in &scored {
+        for (player, score) in [(white, white_score), (black, 1.0 - white_score)] {
+            let standing = &mut standings[player];
+            standing.points += score;
+            standing.games += 1;
+            match score {
+                s if s > 0.5 => standing.wins += 1,
+                s if s < 0.5 => standing.losses += 1,
+                _ => standing.draws += 1,
+            }
+        }
+    }
+
+    let points: Vec<f64> = standings.iter().map(|s| s.points).collect();
+    for &(white, black, white_score) in &scored {
+        for (player, opponent, score) in [(white, black, white_score), (black, white, 1.0 - white_score)] {
+            standings[player].sonneborn_berger += score * points[opponent];
+            standings[player].buchholz += points[opponent];
+        }
+    }
+
+    standings
+}
+
+fn print_tables(state: &TournamentState) {
+    let standings = compute_standings(state);
+    let mut order: Vec<usize> = (0..standings.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&standings[a], &standings[b]);
+        b.points
+            .total_cmp(&a.points)
+            .then(b.sonneborn_berger.total_cmp(&a.sonneborn_berger))
+            .then(b.buchholz.total_cmp(&a.buchholz))
+    });
+
+    let width = state.names.iter().map(|n| n.chars().count()).max().unwrap_or(6).max(6);
+
+    println!("\n🏆 Standings after {} of {} games:", state.finished_games(), state.games.len());
+    println!(
+        "{:>3}  {:<width$}  {:>5}  {:>5}  {:>3}  {:>3}  {:>3}  {:>7}  {:>8}",
+        "#", "Engine", "Pts", "Games", "W", "D", "L", "SB", "Buchholz"
+    );
+    for (rank, &player) in order.iter().enumerate() {
+        let s = &standings[player];
+        println!(
+            "{:>3}  {:<width$}  {:>5.1}  {:>5}  {:>3}  {:>3}  {:>3}  {:>7.2}  {:>8.2}",
+            rank + 1,
+            state.names[player],
+            s.points,
+            s.games,
+            s.wins,
+            s.draws,
+            s.losses,
+            s.sonneborn_berger,
+            s.buchholz
+        );
+    }
+
+    let mut cells: HashMap<(usize, usize), (f64, u32)> = HashMap::new();
+    for game in &state.games {
+        let white_score = match game.result() {
+            Some(GameResult::WhiteWins) => 1.0,
+            Some(GameResult::BlackWins) => 0.0,
+            Some(GameResult::Draw) => 0.5,
+            None => continue,
+        };
+        for (player, opponent, score) in [(game.white, game.black, white_score), (game.black, game.white, 1.0 - white_score)] {
+            let cell = cells.entry((player, opponent)).or_default();
+            cell.0 += score;
+            cell.1 += 1;
+        }
+    }
+
+    println!("\nCrosstable:");
+    print!("{:>3}  {:<width$}", "#", "Engine");
+    for rank in 1..=order.len() {
+        print!("  {:>7}", rank);
+    }
+    println!();
+    for (rank, &player) in order.iter().enumerate() {
+        print!("{:>3}  {:<width$}", rank + 1, state.names[player]);
+        for &opponent in &order {
+            let cell = match cells.get(&(player, opponent)) {
+                _ if player == opponent => "·".to_string(),
+                Some((score, games)) => format!("{:.1}/{}", score, games),
+                None => "-".to_string(),
+            };
+            print!("  {:>7}", cell);
+        }
+        println!();
+    }
+}
+
+pub fn run(args: TournamentArgs) -> Result<()> {
+    if args.engines.len() < 2 {
+        return Err(anyhow!("A tournament needs at least two --engine specs"));
+    }
+    let configs = args.game.engine_configs(&args.engines)?;
+    let settings = GameSettings::from_args(&args.game, &args.event)?;
+
+    let tournament = TournamentSettings {
+        engines: args.engines.clone(),
+        format: args.format,
+        games_per_encounter: args.games_per_encounter.unwrap_or(match args.format {
+            TournamentFormat::RoundRobin | TournamentFormat::DoubleRoundRobin => 1,
+            TournamentFormat::Gauntlet => 2,
+        }),
+        time_control: settings.time_control_tag(),
+        openings: args.game.openings.clone(),
+        opening_plies: args.game.opening_plies,
+        adjudication: settings.adjudication.clone(),
+        syzygy_path: args.game.syzygy_path.clone(),
+    };
+
+    let mut state = if args.state.exists() {
+        let state = TournamentState::load(&args.state)?;
+        if state.settings != tournament {
+            return Err(anyhow!(
+                "'{}' belongs to a tournament with other engines, format, games per encounter, time control, \
+                 openings, adjudication or tablebases; remove it or pass another --state",
+                args.state.display()
+            ));
+        }
+        println!("♻️  Resuming tournament: {} of {} games played", state.finished_games(), state.games.len());
+        state
+    } else {
+        let openings = args.game.load_openings()?;
+        let state = TournamentState {
+            names: configs
+                .iter()
+                .map(|c| c.name.clone().unwrap_or_else(|| c.command.clone()))
+                .collect(),
+            games: build_schedule(args.format, configs.len(), tournament.games_per_encounter, &openings),
+            settings: tournament,
+        };
+        state.save(&args.state)?;
+        println!("🏆 Tournament: {} engines, {} games", configs.len(), state.games.len());
+        state
+    };
+
+    let pgn_path = args.pgn_out.clone().unwrap_or_else(|| args.state.with_extension("pgn"));
+    let mut pgn_out = OpenOptions::new().create(true).append(true).open(&pgn_path)?;
+
+    let pending: VecDeque<usize> = state
+        .games
+        .iter()
+        .enumerate()
+        .filter(|(_, game)| game.result.is_none())
+        .map(|(index, _)| index)
+        .collect();
+    let queue = Mutex::new(pending);
+    let schedule = state
+        .games
+        .iter()
+        .map(|game| Ok((game.white, game.black, game.opening()?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let next_pairing = || {
+        let index = queue.lock().ok()?.pop_front()?;
+        let (white, black, ref opening) = schedule[index];
+        Some(Pairing { index, white, black, opening: opening.clone() })
+    };
+
+    match_runner::play_games(&configs, &settings, args.game.concurrency, next_pairing, |finished| {
+        let mut record = match finished.record {
+            Ok(record) => record,
+            Err(e) => {
+                println!("❌ Game {} failed: {}", finished.index + 1, e);
+                return Ok(());
+            }
+        };
+
+        let game = &mut state.games[finished.index];
+        record.pgn.set_tag("Round", &game.round.to_string());
+        game.result = Some(record.outcome.result.to_pgn().to_string());
+        game.reason = Some(record.outcome.reason.clone());
+        let (white, black) = (game.white, game.black);
+        for (player, tag) in [(white, "White"), (black, "Black")] {
+            if let Some(name) = record.pgn.tag(tag) {
+                state.names[player] = name.to_string();
+            }
+        }
+        state.save(&args.state)?;
+
+        println!(
+            "Finished game {} of {} ({} vs {}): {} {{{}}}",
+            finished.index + 1,
+            state.games.len(),
+            state.names[white],
+            state.names[black],
+            record.outcome.result.to_pgn(),
+            record.outcome.reason
+        );
+
+        match_runner::write_pgn(&mut pgn_out, &record.pgn)?;
+        Ok(())
+    })?;
+
+    print_tables(&state);
+    println!("\n📄 Games saved to {}", pgn_path.display());
+
+    let unfinished = state.games.len() - state.finished_games();
+    if unfinished > 0 {
+        println!("\n⚠️  {} games unfinished; run the same command again to resume.", unfinished);
+    }
+    Ok(())
+}
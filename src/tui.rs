@@ -0,0 +1,634 @@
+use crate::chess::{Board, Move, PieceType, Player, Position};
+use crate::ui::{BoardMarks, BoardSize};
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use std::io::{self, Write};
+use std::panic;
+use std::sync::Once;
+use std::time::Duration;
+
+/// Columns between the board and the panes right of it.
+const SIDE_GAP: u16 = 3;
+/// Columns and rows the panes need beside and below the board.
+pub const SPARE: (u16, u16) = (SIDE_GAP + 42, 6);
+/// Top of the moves pane, below the clocks and game info.
+const MOVES_Y: u16 = 11;
+
+/// Raw mode on the alternate screen for as long as this lives; dropping it, or a panic, restores the terminal.
+pub struct Terminal;
+
+impl Terminal {
+    pub fn enter() -> Result<Self> {
+        static PANIC_HOOK: Once = Once::new();
+        PANIC_HOOK.call_once(|| {
+            let default_hook = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                restore();
+                default_hook(info);
+            }));
+        });
+
+        terminal::enable_raw_mode()?;
+        execute!(
+            io::stdout(),
+            terminal::EnterAlternateScreen,
+            event::EnableMouseCapture,
+            cursor::Hide,
+            terminal::Clear(ClearType::All)
+        )?;
+        Ok(Terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+fn restore() {
+    let _ = execute!(io::stdout(), ResetColor, cursor::Show, event::DisableMouseCapture, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    pub fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    pub fn contains(&self, column: u16, row: u16) -> bool {
+        (self.x..self.x + self.width).contains(&column) && (self.y..self.y + self.height).contains(&row)
+    }
+}
+
+/// Where each pane of the game screen goes: the board with the engine output below it,
+/// clocks, game info and moves to the right, and the status and command lines at the bottom.
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    pub board: Rect,
+    pub engine: Rect,
+    pub clocks: Rect,
+    pub info: Rect,
+    pub moves: Rect,
+    pub status: Rect,
+    pub command: Rect,
+    /// The terminal's columns and rows, and the fewest the panes fit in.
+    pub terminal: (u16, u16),
+    pub minimum: (u16, u16),
+}
+
+impl Layout {
+    pub fn new(columns: u16, rows: u16, board: BoardSize) -> Self {
+        let (board_width, board_height) = (board.width(), board.height());
+        let minimum = (board_width + SPARE.0, (board_height + SPARE.1).max(MOVES_Y + 4));
+        let terminal = (columns, rows);
+        // Panes are laid out for at least the minimum size, but only drawn when it fits.
+        let (columns, rows) = (columns.max(minimum.0), rows.max(minimum.1));
+        let side_x = board_width + SIDE_GAP;
+        let side_width = columns - side_x;
+        let bottom = rows - 2;
+
+        let engine_y = board_height + 1;
+        let moves_y = MOVES_Y;
+        Layout {
+            board: Rect::new(0, 0, board_width, board_height),
+            engine: Rect::new(0, engine_y, side_x - 1, bottom - engine_y),
+            clocks: Rect::new(side_x, 0, side_width, 2),
+            info: Rect::new(side_x, 3, side_width, MOVES_Y - 4),
+            moves: Rect::new(side_x, moves_y, side_width, bottom - moves_y),
+            status: Rect::new(0, bottom, columns, 1),
+            command: Rect::new(0, bottom + 1, columns, 1),
+            terminal,
+            minimum,
+        }
+    }
+
+    pub fn fits(&self) -> bool {
+        self.terminal.0 >= self.minimum.0 && self.terminal.1 >= self.minimum.1
+    }
+
+    pub fn for_terminal(board: BoardSize) -> Self {
+        let (columns, rows) = terminal::size().unwrap_or((80, 24));
+        Layout::new(columns, rows, board)
+    }
+}
+
+/// Which panes need drawing on the next frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Dirty {
+    pub board: bool,
+    pub eval_bar: bool,
+    pub engine: bool,
+    pub clocks: bool,
+    pub info: bool,
+    pub moves: bool,
+    pub status: bool,
+    pub command: bool,
+}
+
+impl Dirty {
+    pub fn all() -> Self {
+        Dirty {
+            board: true,
+            eval_bar: true,
+            engine: true,
+            clocks: true,
+            info: true,
+            moves: true,
+            status: true,
+            command: true,
+        }
+    }
+
+    /// Everything that shows the position, after a move.
+    pub fn position() -> Self {
+        Dirty { status: false, command: false, ..Dirty::all() }
+    }
+
+    pub fn merge(&mut self, other: Dirty) {
+        self.board |= other.board;
+        self.eval_bar |= other.eval_bar;
+        self.engine |= other.engine;
+        self.clocks |= other.clocks;
+        self.info |= other.info;
+        self.moves |= other.moves;
+        self.status |= other.status;
+        self.command |= other.command;
+    }
+}
+
+/// A line of text with an optional color.
+pub type Line = (String, Option<Color>);
+
+/// Draws `lines` into `rect`, padding each row so whatever was there before is overwritten.
+pub fn draw_lines(rect: Rect, lines: &[Line]) -> Result<()> {
+    let mut stdout = io::stdout();
+    for row in 0..rect.height {
+        queue!(stdout, cursor::MoveTo(rect.x, rect.y + row))?;
+        match lines.get(row as usize) {
+            Some((text, color)) => {
+                let (text, width) = truncate(text, rect.width as usize);
+                if let Some(color) = color {
+                    queue!(stdout, SetForegroundColor(*color))?;
+                }
+                queue!(stdout, Print(text), ResetColor, Print(" ".repeat(rect.width as usize - width)))?;
+            }
+            None => queue!(stdout, Print(" ".repeat(rect.width as usize)))?,
+        }
+    }
+    Ok(())
+}
+
+/// Like `draw_lines`, for rows made of differently colored spans.
+pub fn draw_rows(rect: Rect, rows: &[Vec<Line>]) -> Result<()> {
+    let mut stdout = io::stdout();
+    for row in 0..rect.height {
+        queue!(stdout, cursor::MoveTo(rect.x, rect.y + row))?;
+        let mut used = 0;
+        for (text, color) in rows.get(row as usize).map_or(&[][..], Vec::as_slice) {
+            let (text, width) = truncate(text, rect.width as usize - used);
+            used += width;
+            if let Some(color) = color {
+                queue!(stdout, SetForegroundColor(*color))?;
+            }
+            queue!(stdout, Print(text), ResetColor)?;
+        }
+        queue!(stdout, Print(" ".repeat(rect.width as usize - used)))?;
+    }
+    Ok(())
+}
+
+/// Draws a pane title in bold, padded to the width of the pane.
+pub fn draw_title(rect: Rect, title: &str) -> Result<()> {
+    let (text, width) = truncate(title, rect.width as usize);
+    queue!(
+        io::stdout(),
+        cursor::MoveTo(rect.x, rect.y),
+        SetAttribute(Attribute::Bold),
+        Print(text),
+        SetAttribute(Attribute::Reset),
+        Print(" ".repeat(rect.width as usize - width))
+    )?;
+    Ok(())
+}
+
+/// A move for the move list, with its thinking time and evaluation when known.
+pub struct MoveEntry {
+    pub san: String,
+    pub time: Option<Duration>,
+    pub eval: Option<String>,
+}
+
+/// Moves as numbered pairs, one row per move number.
+pub struct MoveList {
+    pub entries: Vec<MoveEntry>,
+    pub first_number: u32,
+    /// The game started with Black to move.
+    pub black_first: bool,
+}
+
+impl MoveList {
+    /// Each row's move number and the indices of White's and Black's moves.
+    fn rows(&self) -> Vec<(u32, Option<usize>, Option<usize>)> {
+        let mut rows = Vec::new();
+        let mut number = self.first_number;
+        let mut index = 0;
+        if self.black_first && !self.entries.is_empty() {
+            rows.push((number, None, Some(0)));
+            number += 1;
+            index = 1;
+        }
+        while index < self.entries.len() {
+            rows.push((number, Some(index), (index + 1 < self.entries.len()).then_some(index + 1)));
+            number += 1;
+            index += 2;
+        }
+        rows
+    }
+
+    /// The `scroll` for `draw` that brings the move at `index`, or the first row, to the middle of `height` rows.
+    pub fn scroll_showing(&self, index: Option<usize>, height: usize) -> usize {
+        let rows = self.rows();
+        let row = index
+            .and_then(|index| rows.iter().position(|&(_, white, black)| white == Some(index) || black == Some(index)))
+            .unwrap_or(0);
+        rows.len().saturating_sub(height + row.saturating_sub(height / 2))
+    }
+
+    /// Draws the rows that fit in `rect`, `scroll` rows back from the latest, with the `current`
+    /// move highlighted. Returns `scroll` limited to what there is to scroll back through.
+    pub fn draw(&self, rect: Rect, current: Option<usize>, scroll: usize) -> Result<usize> {
+        let rows = self.rows();
+        let visible = rect.height as usize;
+        let scroll = scroll.min(rows.len().saturating_sub(visible));
+        let top = rows.len().saturating_sub(visible + scroll);
+        let show_time = self.entries.iter().any(|entry| entry.time.is_some());
+        let show_eval = self.entries.iter().any(|entry| entry.eval.is_some());
+
+        let cell = |index: Option<usize>| {
+            let entry = index.map(|index| &self.entries[index]);
+            let mut text = format!("{:<7}", entry.map_or("...", |entry| entry.san.as_str()));
+            if show_time {
+                let time = entry.and_then(|entry| entry.time).map(format_move_time);
+                text.push_str(&format!("{:>5}", time.unwrap_or_default()));
+            }
+            if show_eval {
+                text.push_str(&format!("{:>6}", entry.and_then(|entry| entry.eval.as_deref()).unwrap_or("")));
+            }
+            text
+        };
+
+        let mut stdout = io::stdout();
+        for row in 0..rect.height {
+            queue!(stdout, cursor::MoveTo(rect.x, rect.y + row))?;
+            let mut used = 0;
+            if let Some(&(number, white, black)) = rows.get(top + row as usize) {
+                let spans = [
+                    (format!("{:>3}.", number), None),
+                    (" ".to_string(), None),
+                    (cell(white), white),
+                    (" ".to_string(), None),
+                    (cell(black), black),
+                ];
+                for (span_index, (text, index)) in spans.into_iter().enumerate() {
+                    let (text, width) = truncate(&text, rect.width as usize - used);
+                    used += width;
+                    if span_index == 0 {
+                        queue!(stdout, SetForegroundColor(Color::DarkGrey))?;
+                    }
+                    if index.is_some() && index == current {
+                        queue!(stdout, SetAttribute(Attribute::Reverse))?;
+                    }
+                    queue!(stdout, Print(text), SetAttribute(Attribute::Reset), ResetColor)?;
+                }
+            }
+            queue!(stdout, Print(" ".repeat(rect.width as usize - used)))?;
+        }
+        Ok(scroll)
+    }
+}
+
+/// `4.2s`, `17s` or `1:05`.
+fn format_move_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    match seconds {
+        0..=9 => format!("{:.1}s", time.as_secs_f64()),
+        10..=59 => format!("{}s", seconds),
+        _ => format!("{}:{:02}", seconds / 60, seconds % 60),
+    }
+}
+
+/// Shows `lines` over the whole screen until the next key press.
+pub fn draw_overlay(lines: &[&str]) -> Result<()> {
+    let (columns, rows) = terminal::size().unwrap_or((80, 24));
+    execute!(io::stdout(), terminal::Clear(ClearType::All))?;
+    let mut lines: Vec<Line> = lines.iter().map(|line| (line.to_string(), None)).collect();
+    lines.push((String::new(), None));
+    lines.push(("Press any key to continue".to_string(), Some(Color::DarkGrey)));
+    draw_lines(Rect::new(0, 0, columns, rows), &lines)?;
+    io::stdout().flush()?;
+    Ok(())
+}
+
+/// Replaces the screen with a request to enlarge the terminal until `layout` fits.
+pub fn draw_too_small(layout: &Layout) -> Result<()> {
+    let (columns, rows) = layout.terminal;
+    let text = format!(
+        "Terminal too small: {}x{} needed, {}x{} available. Enlarge the window to continue.",
+        layout.minimum.0, layout.minimum.1, columns, rows
+    );
+    let lines: Vec<Line> = wrap(&text, columns as usize).into_iter().map(|line| (line, Some(Color::Yellow))).collect();
+    execute!(io::stdout(), terminal::Clear(ClearType::All))?;
+    draw_lines(Rect::new(0, 0, columns, rows), &lines)?;
+    io::stdout().flush()?;
+    Ok(())
+}
+
+/// Cells a character takes on screen: emoji are double width, variation selectors take none.
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0xFE00..=0xFE0F | 0x200D => 0,
+        0x1F000.. => 2,
+        _ => 1,
+    }
+}
+
+pub fn text_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// The longest prefix of `text` that fits in `width` cells, and the cells it takes.
+fn truncate(text: &str, width: usize) -> (String, usize) {
+    let mut used = 0;
+    let mut prefix = String::new();
+    for c in text.chars() {
+        let cells = char_width(c);
+        if used + cells > width {
+            break;
+        }
+        used += cells;
+        prefix.push(c);
+    }
+    (prefix, used)
+}
+
+/// Breaks `text` into lines of at most `width` cells at spaces.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && text_width(&line) + 1 + text_width(word) > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// The editable input line at the bottom of the screen, with a history of submitted commands.
+#[derive(Debug, Default)]
+pub struct CommandLine {
+    text: Vec<char>,
+    cursor: usize,
+    history: Vec<String>,
+    history_index: usize,
+}
+
+impl CommandLine {
+    /// Applies a key press; returns the submitted line when Enter is pressed.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<String> {
+        match key.code {
+            KeyCode::Enter => {
+                let line: String = self.text.drain(..).collect();
+                self.cursor = 0;
+                if !line.trim().is_empty() && self.history.last() != Some(&line) {
+                    self.history.push(line.clone());
+                }
+                self.history_index = self.history.len();
+                return Some(line);
+            }
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.text.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            KeyCode::Char(c) => {
+                self.text.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.text.len() => {
+                self.text.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.text.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            KeyCode::Up if self.history_index > 0 => {
+                self.history_index -= 1;
+                self.recall();
+            }
+            KeyCode::Down if self.history_index < self.history.len() => {
+                self.history_index += 1;
+                self.recall();
+            }
+            KeyCode::Esc => {
+                self.text.clear();
+                self.cursor = 0;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    fn recall(&mut self) {
+        self.text = self.history.get(self.history_index).map_or_else(Vec::new, |line| line.chars().collect());
+        self.cursor = self.text.len();
+    }
+
+    /// Draws the prompt and the input, leaving the terminal cursor where the next character goes.
+    pub fn draw(&self, rect: Rect, prompt: &str) -> Result<()> {
+        let text: String = self.text.iter().collect();
+        draw_lines(rect, &[(format!("{}{}", prompt, text), None)])?;
+        let before_cursor: String = self.text[..self.cursor].iter().collect();
+        let column = rect.x as usize + text_width(prompt) + text_width(&before_cursor);
+        queue!(io::stdout(), cursor::MoveTo(column.min(rect.width.saturating_sub(1) as usize) as u16, rect.y), cursor::Show)?;
+        Ok(())
+    }
+}
+
+/// What a key press on the board did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CursorAction {
+    /// The cursor or selection changed.
+    Changed,
+    /// A move was entered.
+    Play(Move),
+    /// The key is not a board key; it belongs to the command line.
+    Unhandled,
+}
+
+/// Move entry from the keyboard: a cursor over the squares, the selected piece, and a pawn waiting to promote.
+#[derive(Debug, Clone)]
+pub struct BoardCursor {
+    pub square: Position,
+    pub selected: Option<Position>,
+    /// Destination of a promoting pawn, while the promotion piece is picked.
+    pub promotion: Option<Position>,
+}
+
+impl BoardCursor {
+    /// Starts on the king's pawn of `player`.
+    pub fn new(player: Player) -> Self {
+        let rank = if player == Player::White { 1 } else { 6 };
+        BoardCursor { square: Position { file: 4, rank }, selected: None, promotion: None }
+    }
+
+    /// Applies a key; the arrows move across the screen, so their direction depends on `orientation`.
+    pub fn handle_key(&mut self, key: KeyEvent, board: &Board, orientation: Player) -> CursorAction {
+        if let Some(to) = self.promotion {
+            let piece_type = match key.code {
+                KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Char('q') => PieceType::Queen,
+                KeyCode::Char('r') => PieceType::Rook,
+                KeyCode::Char('b') => PieceType::Bishop,
+                KeyCode::Char('n') => PieceType::Knight,
+                KeyCode::Esc => {
+                    self.promotion = None;
+                    return CursorAction::Changed;
+                }
+                _ => return CursorAction::Changed,
+            };
+            return self.play(board, to, Some(piece_type));
+        }
+
+        let sign: i8 = if orientation == Player::White { 1 } else { -1 };
+        let (file, rank) = (self.square.file as i8, self.square.rank as i8);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.square.rank = (rank + sign).clamp(0, 7) as u8,
+            KeyCode::Down | KeyCode::Char('j') => self.square.rank = (rank - sign).clamp(0, 7) as u8,
+            KeyCode::Left | KeyCode::Char('h') => self.square.file = (file - sign).clamp(0, 7) as u8,
+            KeyCode::Right | KeyCode::Char('l') => self.square.file = (file + sign).clamp(0, 7) as u8,
+            KeyCode::Enter | KeyCode::Char(' ') => return self.select(board, self.square),
+            KeyCode::Esc if self.selected.is_some() => self.selected = None,
+            _ => return CursorAction::Unhandled,
+        }
+        CursorAction::Changed
+    }
+
+    /// Selects a piece of the side to move, or moves the selected piece to `square`.
+    pub fn select(&mut self, board: &Board, square: Position) -> CursorAction {
+        self.square = square;
+        self.promotion = None;
+        let own_piece = board.piece_at(square).is_some_and(|piece| piece.player == board.current_player());
+        match self.selected {
+            Some(from) if from == square => self.selected = None,
+            Some(from) if self.targets(board).contains(&square) => {
+                let promotes = board
+                    .get_legal_moves()
+                    .iter()
+                    .any(|m| m.from() == from && m.to() == square && m.promotion().is_some());
+                if promotes {
+                    self.promotion = Some(square);
+                } else {
+                    return self.play(board, square, None);
+                }
+            }
+            _ if own_piece => self.selected = Some(square),
+            _ => self.selected = None,
+        }
+        CursorAction::Changed
+    }
+
+    /// Finishes a pending promotion with `piece_type`.
+    pub fn promote(&mut self, board: &Board, piece_type: PieceType) -> CursorAction {
+        match self.promotion {
+            Some(to) => self.play(board, to, Some(piece_type)),
+            None => CursorAction::Unhandled,
+        }
+    }
+
+    /// The promotion piece under a click on the status line, where `hint` lists the choices.
+    pub fn promotion_choice_at(&self, status: Rect, column: u16, row: u16) -> Option<PieceType> {
+        let square = self.promotion?;
+        if row != status.y {
+            return None;
+        }
+        let start = status.x as usize + text_width(&promotion_prompt(square));
+        let offset = (column as usize).checked_sub(start)?;
+        // The choices are single glyphs separated by spaces.
+        if offset % 2 != 0 {
+            return None;
+        }
+        PROMOTION_CHOICES.get(offset / 2).copied()
+    }
+
+    fn play(&mut self, board: &Board, to: Position, promotion: Option<PieceType>) -> CursorAction {
+        let from = self.selected.take();
+        self.promotion = None;
+        match from {
+            Some(from) if board.find_legal_move(from, to, promotion).is_some() => {
+                CursorAction::Play(Move::new(from, to, promotion))
+            }
+            _ => CursorAction::Changed,
+        }
+    }
+
+    /// Legal destinations of the selected piece.
+    pub fn targets(&self, board: &Board) -> Vec<Position> {
+        let Some(from) = self.selected else {
+            return Vec::new();
+        };
+        let mut targets: Vec<Position> =
+            board.get_legal_moves().iter().filter(|m| m.from() == from).map(|m| m.to()).collect();
+        targets.dedup();
+        targets
+    }
+
+    pub fn marks(&self, board: &Board) -> BoardMarks {
+        BoardMarks { cursor: Some(self.square), selected: self.selected, targets: self.targets(board), ..BoardMarks::default() }
+    }
+
+    /// A reminder of the keys for the current state.
+    pub fn hint(&self) -> String {
+        match (self.promotion, self.selected) {
+            (Some(square), _) => format!(
+                "{}♕ ♖ ♗ ♘  (click one, or q, r, b, n; Enter for a queen; Esc cancels)",
+                promotion_prompt(square)
+            ),
+            (None, Some(square)) => format!("🎯 {} selected: click a target or move there and press Enter; Esc cancels", square.to_algebraic()),
+            (None, None) => "⌨️  Arrows/hjkl move, Enter or Space selects a piece; Tab returns to typing".to_string(),
+        }
+    }
+}
+
+const PROMOTION_CHOICES: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+fn promotion_prompt(square: Position) -> String {
+    format!("Promote on {}: ", square.to_algebraic())
+}
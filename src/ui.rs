@@ -1,93 +1,483 @@
-use crate::chess::{Board, GameState, Player};
+use crate::chess::{Board, GameState, Move, Piece, PieceType, Player, Position, STARTING_FEN};
+use crate::annotate;
+use crate::eco;
+use crate::engine::Score;
+use crate::syzygy::{Tablebase, Wdl};
+use crate::theme::Appearance;
+use crate::tui::Line;
 use anyhow::Result;
+use clap::ValueEnum;
 use crossterm::{
-    cursor, execute,
-    style::{Color, ResetColor, SetForegroundColor},
-    terminal::{self, ClearType},
+    cursor, queue,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal,
 };
-use std::io;
+use std::cell::RefCell;
+use std::io::{self, Write};
 
-pub struct ChessUI;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BoardSize {
+    /// One line per rank, squares told apart by their background
+    Small,
+    /// One line per rank inside a grid
+    Medium,
+    /// Three lines per rank with ASCII-art pieces
+    Large,
+}
+
+impl BoardSize {
+    /// Columns and rows inside each square.
+    fn cell(self) -> (u16, u16) {
+        match self {
+            BoardSize::Small | BoardSize::Medium => (3, 1),
+            BoardSize::Large => (7, 3),
+        }
+    }
+
+    fn has_grid(self) -> bool {
+        self == BoardSize::Medium
+    }
+
+    /// Columns and rows from one square to the next.
+    fn pitch(self) -> (u16, u16) {
+        let (width, height) = self.cell();
+        let grid = self.has_grid() as u16;
+        (width + grid, height + grid)
+    }
+
+    /// Terminal cells taken by the board, including the rank and file labels.
+    pub fn width(self) -> u16 {
+        8 * self.pitch().0 + self.has_grid() as u16 + 2
+    }
+
+    pub fn height(self) -> u16 {
+        8 * self.pitch().1 + self.has_grid() as u16 + 1
+    }
+}
+
+/// Centipawns at which the eval bar is all but full.
+const EVAL_BAR_CAP: i32 = 1000;
+
+/// Pieces for large squares, five columns by three rows; `%` fills the black pieces.
+fn piece_art(piece_type: PieceType) -> [&'static str; 3] {
+    match piece_type {
+        PieceType::King => [" _+_ ", " \\%/ ", " /%\\ "],
+        PieceType::Queen => [" \\|/ ", " )%( ", " /%\\ "],
+        PieceType::Rook => [" n_n ", " |%| ", " /%\\ "],
+        PieceType::Bishop => ["  ^  ", " (%) ", " /%\\ "],
+        PieceType::Knight => ["  /) ", " /%| ", " /%\\ "],
+        PieceType::Pawn => ["     ", "  o  ", " /%\\ "],
+    }
+}
+
+/// Squares to mark when drawing the board.
+#[derive(Debug, Clone, Default)]
+pub struct BoardMarks {
+    pub cursor: Option<Position>,
+    pub selected: Option<Position>,
+    /// Legal destinations of the selected piece.
+    pub targets: Vec<Position>,
+    /// The engine's suggestion, drawn with an arrow on its starting square.
+    pub best_move: Option<Move>,
+}
+
+pub struct ChessUI {
+    /// The side drawn at the bottom of the board.
+    orientation: Player,
+    appearance: Appearance,
+    size: BoardSize,
+    show_eval_bar: bool,
+    /// The last tablebase line and the FEN it was probed for; DTZ probes search.
+    tablebase_cache: RefCell<Option<(String, Line)>>,
+}
 
 impl ChessUI {
-    pub fn new() -> Self {
-        ChessUI
+    pub fn new(appearance: Appearance) -> Self {
+        ChessUI {
+            orientation: Player::White,
+            appearance,
+            size: appearance.board_size.unwrap_or(BoardSize::Medium),
+            show_eval_bar: appearance.eval_bar,
+            tablebase_cache: RefCell::new(None),
+        }
     }
     
-    pub fn display_board(&self, board: &Board) -> Result<()> {
-        execute!(io::stdout(), cursor::MoveTo(0, 0), terminal::Clear(ClearType::All))?;
+    pub fn orientation(&self) -> Player {
+        self.orientation
+    }
         
-        println!("┌───┬───┬───┬───┬───┬───┬───┬───┐");
+    pub fn set_orientation(&mut self, player: Player) {
+        self.orientation = player;
+    }
         
-        for rank in (0..8).rev() {
-            print!("│");
-            for file in 0..8 {
-                let pos = crate::chess::Position::new(file, rank)?;
-                let piece_char = if let Some(piece) = board.piece_at(pos) {
-                    piece.unicode_symbol()
-                } else {
-                    ' '
-                };
+    pub fn flip(&mut self) {
+        self.orientation = self.orientation.opposite();
+    }
                 
-                let is_light_square = (file + rank) % 2 == 0;
-                if is_light_square {
-                    execute!(io::stdout(), SetForegroundColor(Color::White))?;
-                } else {
-                    execute!(io::stdout(), SetForegroundColor(Color::Yellow))?;
+    pub fn show_eval_bar(&self) -> bool {
+        self.show_eval_bar
+    }
+
+    pub fn toggle_eval_bar(&mut self) {
+        self.show_eval_bar = !self.show_eval_bar;
+    }
+
+    pub fn size(&self) -> BoardSize {
+        self.size
+    }
+
+    /// Picks the largest board leaving `spare` columns and rows of the screen free, unless a size was chosen.
+    pub fn fit(&mut self, columns: u16, rows: u16, spare: (u16, u16)) {
+        let fits = |size: &BoardSize| size.width() + spare.0 <= columns && size.height() + spare.1 <= rows;
+        let largest = [BoardSize::Large, BoardSize::Medium].into_iter().find(fits);
+        self.size = self.appearance.board_size.or(largest).unwrap_or(BoardSize::Small);
+    }
+
+    pub fn fit_terminal(&mut self, spare: (u16, u16)) {
+        let (columns, rows) = terminal::size().unwrap_or((80, 24));
+        self.fit(columns, rows, spare);
+    }
+
+    /// Ranks top to bottom and files left to right, as seen from the bottom side.
+    fn view_order(&self) -> ([u8; 8], [u8; 8]) {
+        let ascending = [0, 1, 2, 3, 4, 5, 6, 7];
+        let descending = [7, 6, 5, 4, 3, 2, 1, 0];
+        match self.orientation {
+            Player::White => (descending, ascending),
+            Player::Black => (ascending, descending),
+        }
+    }
+
+    /// Draws the board with its top-left corner at `column`, `row`, addressing every line, so it works in raw mode.
+    pub fn draw_board(&self, board: &Board, marks: &BoardMarks, column: u16, row: u16) -> Result<()> {
+        let mut stdout = io::stdout();
+        let mut line = row;
+        let mut next_line = |stdout: &mut io::Stdout| -> Result<()> {
+            queue!(stdout, cursor::MoveTo(column, line))?;
+            line += 1;
+            Ok(())
+        };
+
+        let size = self.size;
+        let (cell_width, cell_height) = size.cell();
+        let grid = |left: &str, middle: &str, right: &str| {
+            let segment = "─".repeat(cell_width as usize);
+            format!("{}{}{}", left, vec![segment; 8].join(middle), right)
+        };
+        if size.has_grid() {
+            next_line(&mut stdout)?;
+            queue!(stdout, Print(grid("┌", "┬", "┐")))?;
+        }
+
+        let (ranks, files) = self.view_order();
+        let palette = &self.appearance.palette;
+        let pieces = self.appearance.pieces;
+        let last_move = board.move_history().last().map(|last| (last.from(), last.to()));
+        let check = board.king_position(board.current_player()).filter(|_| board.is_in_check(board.current_player()));
+        let best_move = marks.best_move.as_ref().map(|best| (best.from(), best.to()));
+
+        for (row_index, &rank) in ranks.iter().enumerate() {
+            for cell_line in 0..cell_height {
+                let middle_line = cell_line == cell_height / 2;
+                next_line(&mut stdout)?;
+                if size.has_grid() {
+                    queue!(stdout, Print("│"))?;
                 }
+                for &file in &files {
+                    let pos = Position::new(file, rank)?;
+                    let piece = board.piece_at(pos);
                 
-                print!(" {} ", piece_char);
-                execute!(io::stdout(), ResetColor)?;
-                print!("│");
+                    let is_light_square = (file + rank) % 2 == 1;
+                    let shade = |(light, dark): (Color, Color)| if is_light_square { light } else { dark };
+                    let mut background = shade(palette.squares);
+                    if last_move.is_some_and(|(from, to)| pos == from || pos == to) {
+                        background = shade(palette.last_move);
+                    }
+                    if best_move.is_some_and(|(from, to)| pos == from || pos == to) {
+                        background = shade(palette.best_move);
+                    }
+                    if check == Some(pos) {
+                        background = shade(palette.check);
+                    }
+                    if marks.selected == Some(pos) {
+                        background = shade(palette.selected);
+                    }
+
+                    let mut cell = vec![' '; cell_width as usize];
+                    let center = cell.len() / 2;
+                    match piece {
+                        Some(piece) if size == BoardSize::Large => {
+                            let fill = if piece.player == Player::Black { "#" } else { " " };
+                            let art = piece_art(piece.piece_type)[cell_line as usize].replace('%', fill);
+                            for (index, c) in art.chars().enumerate() {
+                                cell[center - 2 + index] = c;
+                            }
+                        }
+                        Some(piece) if middle_line => cell[center] = pieces.glyph(piece),
+                        _ => {}
+                    }
+
+                    // The columns either side of the piece carry the target and arrow markers.
+                    let is_target = marks.targets.contains(&pos);
+                    if middle_line {
+                        match piece {
+                            Some(_) if is_target => cell[0] = '×',
+                            None if is_target => cell[center] = '·',
+                            _ => {}
+                        }
+                        if let Some((from, to)) = best_move.filter(|&(from, _)| from == pos) {
+                            cell[cell_width as usize - 1] = self.arrow(from, to);
+                        }
+                    }
+                    let ink = match piece {
+                        Some(piece) if pieces.colored_by_side() && piece.player == Player::White => palette.white_piece,
+                        Some(_) => palette.black_piece,
+                        None => palette.target,
+                    };
+
+                    let (left, rest) = cell.split_at(1);
+                    let (middle, right) = rest.split_at(rest.len() - 1);
+                    queue!(stdout, SetBackgroundColor(background))?;
+                    if marks.cursor == Some(pos) {
+                        queue!(stdout, SetAttribute(Attribute::Reverse))?;
+                    }
+                    queue!(
+                        stdout,
+                        SetForegroundColor(palette.target),
+                        Print(left[0]),
+                        SetForegroundColor(ink),
+                        Print(middle.iter().collect::<String>()),
+                        SetForegroundColor(palette.black_piece),
+                        Print(right[0]),
+                        SetAttribute(Attribute::Reset),
+                        ResetColor
+                    )?;
+                    if size.has_grid() {
+                        queue!(stdout, Print("│"))?;
+                    }
+                }
+                if middle_line {
+                    queue!(stdout, Print(format!(" {}", rank + 1)))?;
+                }
             }
-            println!(" {}", rank + 1);
             
-            if rank > 0 {
-                println!("├───┼───┼───┼───┼───┼───┼───┼───┤");
+            if size.has_grid() && row_index < 7 {
+                next_line(&mut stdout)?;
+                queue!(stdout, Print(grid("├", "┼", "┤")))?;
             }
         }
         
-        println!("└───┴───┴───┴───┴───┴───┴───┴───┘");
-        println!("  a   b   c   d   e   f   g   h  ");
-        println!();
+        if size.has_grid() {
+            next_line(&mut stdout)?;
+            queue!(stdout, Print(grid("└", "┴", "┘")))?;
+        }
+        next_line(&mut stdout)?;
+        let border = if size.has_grid() { " " } else { "" };
+        let labels: String = files
+            .iter()
+            .map(|&file| format!("{}{:^width$}", border, (b'a' + file) as char, width = cell_width as usize))
+            .collect();
+        queue!(stdout, Print(labels))?;
+        stdout.flush()?;
         
         Ok(())
     }
     
-    pub fn display_game_info(&self, board: &Board) -> Result<()> {
+    /// Draws the evaluation `score` (from `side`'s point of view) as a bar filling up from the bottom
+    /// side's end, in the gap right of a board drawn at `column`, `row`. Leaves the cursor where it was.
+    pub fn draw_eval_bar(&self, score: Option<Score>, side: Player, column: u16, row: u16) -> Result<()> {
+        if !self.show_eval_bar {
+            return Ok(());
+        }
+        let side_share = match score {
+            // Mate in 0: `side` has been mated.
+            Some(Score::Mate(0)) => 0.0,
+            Some(Score::Mate(moves)) => (moves > 0) as u8 as f64,
+            Some(score) => annotate::win_percent(score.as_centipawns().clamp(-EVAL_BAR_CAP, EVAL_BAR_CAP)) / 100.0,
+            None => 0.5,
+        };
+        let white_share = match side {
+            Player::White => side_share,
+            Player::Black => 1.0 - side_share,
+        };
+        let palette = &self.appearance.palette;
+        let (bottom_share, bottom, top) = match self.orientation {
+            Player::White => (white_share, palette.white_piece, palette.black_piece),
+            Player::Black => (1.0 - white_share, palette.black_piece, palette.white_piece),
+        };
+
+        // Eighth blocks give the boundary row its share of the height.
+        const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+        let height = self.size.height() - 1;
+        let eighths = (bottom_share * height as f64 * 8.0).round() as u16;
+        let mut stdout = io::stdout();
+        queue!(stdout, cursor::SavePosition, SetForegroundColor(bottom), SetBackgroundColor(top))?;
+        for line in 0..height {
+            let below = height - 1 - line;
+            let filled = eighths.saturating_sub(below * 8).min(8);
+            queue!(stdout, cursor::MoveTo(column + self.size.width() + 1, row + line), Print(BLOCKS[filled as usize]))?;
+        }
+        queue!(stdout, ResetColor, cursor::RestorePosition)?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    /// An arrow pointing from `from` towards `to` as the board is drawn.
+    fn arrow(&self, from: Position, to: Position) -> char {
+        let sign = match self.orientation {
+            Player::White => 1,
+            Player::Black => -1,
+        };
+        let right = (to.file as i8 - from.file as i8).signum() * sign;
+        let up = (to.rank as i8 - from.rank as i8).signum() * sign;
+        match (right, up) {
+            (1, 1) => '↗',
+            (1, 0) => '→',
+            (1, _) => '↘',
+            (0, 1) => '↑',
+            (0, _) => '↓',
+            (_, 1) => '↖',
+            (_, 0) => '←',
+            _ => '↙',
+        }
+    }
+
+    /// The square drawn under terminal cell `column`, `row` by `draw_board` at `origin`.
+    pub fn square_at(&self, origin: (u16, u16), column: u16, row: u16) -> Option<Position> {
+        let mut x = column.checked_sub(origin.0)?;
+        let mut y = row.checked_sub(origin.1)?;
+        let (cell_width, cell_height) = self.size.cell();
+        let (pitch_x, pitch_y) = self.size.pitch();
+        // Grid lines surround the squares of the medium board.
+        if self.size.has_grid() {
+            x = x.checked_sub(1)?;
+            y = y.checked_sub(1)?;
+            if x % pitch_x == cell_width || y % pitch_y == cell_height {
+                return None;
+            }
+        }
+        let (ranks, files) = self.view_order();
+        let file = *files.get((x / pitch_x) as usize)?;
+        let rank = *ranks.get((y / pitch_y) as usize)?;
+        Position::new(file, rank).ok()
+    }
+
+    /// Side to move, captured pieces, check and game state, as colored lines for the info pane.
+    pub fn game_info(&self, board: &Board, tablebase: Option<&Tablebase>) -> Vec<(String, Option<Color>)> {
         let current_player = match board.current_player() {
             Player::White => "White",
             Player::Black => "Black",
         };
         
-        println!("Turn: {} | Move: {}", current_player, board.move_count());
+        let mut lines = vec![(format!("Turn: {} | Move: {}", current_player, board.move_count()), None)];
+        if let Some(opening) = eco::classify(board) {
+            lines.push((format!("📖 {}", opening), None));
+        }
+
+        let from_start = board.starting_fen() == STARTING_FEN;
+        let captured = [Player::White, Player::Black].map(|player| captured_by(board, player, from_start));
+        if captured.iter().any(|pieces| !pieces.is_empty()) {
+            let balance = material(board, Player::White) as i32 - material(board, Player::Black) as i32;
+            for (player, pieces) in [Player::White, Player::Black].into_iter().zip(captured) {
+                let glyphs: String = pieces.iter().map(|piece| self.appearance.pieces.glyph(piece)).collect();
+                let lead = if player == Player::White { balance } else { -balance };
+                let lead = if lead > 0 { format!(" +{}", lead) } else { String::new() };
+                lines.push((format!("{:?} captured: {}{}", player, glyphs, lead), None));
+            }
+        }
         
         if board.is_in_check(board.current_player()) {
-            execute!(io::stdout(), SetForegroundColor(Color::Red))?;
-            println!("⚠️  CHECK!");
-            execute!(io::stdout(), ResetColor)?;
+            lines.push(("⚠️  CHECK!".to_string(), Some(Color::Red)));
         }
         
         match board.game_state() {
-            GameState::Checkmate => {
-                execute!(io::stdout(), SetForegroundColor(Color::Red))?;
-                println!("🏁 CHECKMATE!");
-                execute!(io::stdout(), ResetColor)?;
-            }
-            GameState::Stalemate => {
-                execute!(io::stdout(), SetForegroundColor(Color::Yellow))?;
-                println!("🤝 STALEMATE!");
-                execute!(io::stdout(), ResetColor)?;
-            }
-            GameState::Draw => {
-                execute!(io::stdout(), SetForegroundColor(Color::Yellow))?;
-                println!("🤝 DRAW!");
-                execute!(io::stdout(), ResetColor)?;
-            }
+            GameState::Checkmate => lines.push(("🏁 CHECKMATE!".to_string(), Some(Color::Red))),
+            GameState::Stalemate => lines.push(("🤝 STALEMATE!".to_string(), Some(Color::Yellow))),
+            GameState::Draw => lines.push(("🤝 DRAW!".to_string(), Some(Color::Yellow))),
             GameState::InProgress => {}
         }
         
-        println!();
-        Ok(())
+        if let Some(tablebase) = tablebase.filter(|tb| tb.covers(board)) {
+            lines.push(self.tablebase_line(board, tablebase));
+        }
+        lines
+    }
+
+    fn tablebase_line(&self, board: &Board, tablebase: &Tablebase) -> (String, Option<Color>) {
+        let fen = board.to_fen();
+        if let Some((cached_fen, line)) = self.tablebase_cache.borrow().as_ref() {
+            if *cached_fen == fen {
+                return line.clone();
+            }
+        }
+        let line = probe_tablebase(board, tablebase);
+        *self.tablebase_cache.borrow_mut() = Some((fen, line.clone()));
+        line
+    }
+}
+
+fn probe_tablebase(board: &Board, tablebase: &Tablebase) -> Line {
+    let wdl = match tablebase.probe_wdl(board) {
+        Ok(wdl) => wdl,
+        Err(e) => return (format!("📚 Tablebase: {}", e), None),
+    };
+
+    let mover = board.current_player();
+    let (winner, color) = match wdl {
+        Wdl::Win | Wdl::CursedWin => (Some(mover), Color::Green),
+        Wdl::Loss | Wdl::BlessedLoss => (Some(mover.opposite()), Color::Green),
+        Wdl::Draw => (None, Color::Yellow),
+    };
+    let mut text = match winner {
+        Some(Player::White) => format!("White wins ({} for {:?} to move)", wdl, mover),
+        Some(Player::Black) => format!("Black wins ({} for {:?} to move)", wdl, mover),
+        None => "Draw".to_string(),
+    };
+    if let Ok(dtz) = tablebase.probe_dtz(board) {
+        if dtz != 0 {
+            text.push_str(&format!(", DTZ {}", dtz.abs()));
+        }
     }
+
+    (format!("📚 Tablebase: {}", text), Some(color))
+}
+
+/// Each side's pieces at the start of a game, apart from the king.
+const STARTING_SET: [(PieceType, u32); 5] =
+    [(PieceType::Pawn, 8), (PieceType::Knight, 2), (PieceType::Bishop, 2), (PieceType::Rook, 2), (PieceType::Queen, 1)];
+
+fn count(board: &Board, piece: Piece) -> u32 {
+    Position::all().filter(|&pos| board.piece_at(pos) == Some(&piece)).count() as u32
+}
+
+/// The opponent's pieces missing from the starting set, least valuable first. Pieces beyond the
+/// starting set are promoted pawns, so those pawns are not counted as captured. Games set up from
+/// another position only count the captures in their move history.
+fn captured_by(board: &Board, player: Player, from_start: bool) -> Vec<Piece> {
+    let opponent = player.opposite();
+    if !from_start {
+        let mut captured: Vec<Piece> = board.captured_pieces().into_iter().filter(|piece| piece.player == opponent).collect();
+        captured.sort_by_key(|piece| piece.piece_type.value());
+        return captured;
+    }
+    let counts = STARTING_SET.map(|(piece_type, start)| (piece_type, start, count(board, Piece::new(piece_type, opponent))));
+    let promoted: u32 = counts.iter().map(|&(_, start, count)| count.saturating_sub(start)).sum();
+
+    let mut captured = Vec::new();
+    for (piece_type, start, count) in counts {
+        let missing = match piece_type {
+            PieceType::Pawn => start.saturating_sub(count + promoted),
+            _ => start.saturating_sub(count),
+        };
+        captured.extend((0..missing).map(|_| Piece::new(piece_type, opponent)));
+    }
+    captured
+}
+
+fn material(board: &Board, player: Player) -> u32 {
+    Position::all()
+        .filter_map(|pos| board.piece_at(pos))
+        .filter(|piece| piece.player == player)
+        .map(|piece| piece.piece_type.value())
+        .sum()
 }
\ No newline at end of file
@@ -0,0 +1,326 @@
+use crate::chess::{Board, Player};
+use crate::eval_graph;
+use crate::pgn::{self, PgnGame, PgnMove};
+use crate::theme::ThemeArgs;
+use crate::tui::{self, CommandLine, Layout, Line, MoveEntry, MoveList, Rect};
+use crate::ui::{BoardMarks, ChessUI};
+use anyhow::{anyhow, Result};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::Color,
+    terminal::{self, ClearType},
+};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct ViewArgs {
+    /// PGN file to replay; may hold any number of games
+    pub file: PathBuf,
+
+    /// Game to open first (1-based)
+    #[arg(short, long, default_value = "1")]
+    pub game: usize,
+
+    #[command(flatten)]
+    pub theme: ThemeArgs,
+}
+
+/// A game loaded for replay, with the position before every mainline move.
+struct Replay {
+    index: usize,
+    game: PgnGame,
+    boards: Vec<Board>,
+    ply: usize,
+    /// Evaluations from an annotated game's `[%eval]` comments.
+    evals: Option<Vec<Option<f64>>>,
+}
+
+impl Replay {
+    fn load(games: &[PgnGame], index: usize) -> Result<Self> {
+        let game = games
+            .get(index)
+            .ok_or_else(|| anyhow!("No game {} (the file has {})", index + 1, games.len()))?
+            .clone();
+        let boards = game.mainline_boards().map_err(|e| anyhow!("Game {}: {}", index + 1, e))?;
+        let evals = eval_graph::game_evals(&game);
+        Ok(Replay { index, game, boards, ply: 0, evals })
+    }
+
+    fn board(&self) -> &Board {
+        &self.boards[self.ply]
+    }
+
+    fn last_ply(&self) -> usize {
+        self.boards.len() - 1
+    }
+}
+
+pub fn run(args: ViewArgs) -> Result<()> {
+    let appearance = args.theme.appearance()?;
+    let text = fs::read_to_string(&args.file)
+        .map_err(|e| anyhow!("Failed to read '{}': {}", args.file.display(), e))?;
+    let games = pgn::parse_games(&text)?;
+    if games.is_empty() {
+        return Err(anyhow!("No games found in '{}'", args.file.display()));
+    }
+
+    let mut replay = Replay::load(&games, args.game.saturating_sub(1))?;
+    let mut ui = ChessUI::new(appearance);
+    let _terminal = tui::Terminal::enter()?;
+    let mut view = ReplayView::new(&mut ui, &replay);
+
+    loop {
+        view.draw(&ui, &replay, games.len())?;
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            Event::Resize(..) => {
+                view.fit(&mut ui, &replay)?;
+                continue;
+            }
+            _ => continue,
+        };
+        if view.showing_list {
+            view.showing_list = false;
+            execute!(io::stdout(), terminal::Clear(ClearType::All))?;
+            continue;
+        }
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            break;
+        }
+
+        // Keys step through the game unless a command is being typed.
+        let shortcut = match key.code {
+            _ if !view.command.is_empty() => None,
+            KeyCode::Right => Some("n"),
+            KeyCode::Left => Some("p"),
+            KeyCode::Home => Some("f"),
+            KeyCode::End => Some("l"),
+            KeyCode::PageDown => Some("next-game"),
+            KeyCode::PageUp => Some("prev-game"),
+            _ => None,
+        };
+        let input = match shortcut {
+            Some(command) => command.to_string(),
+            None => match view.command.handle_key(key) {
+                Some(line) => line.trim().to_lowercase(),
+                None => continue,
+            },
+        };
+        let mut words = input.split_whitespace();
+        view.message = (String::new(), None);
+
+        let mut load = |number: usize, replay: &mut Replay| match Replay::load(&games, number) {
+            Ok(loaded) => *replay = loaded,
+            Err(e) => view.message = (format!("❌ {}", e), Some(Color::Red)),
+        };
+        match words.next().unwrap_or("n") {
+            "n" | "next" => replay.ply = (replay.ply + 1).min(replay.last_ply()),
+            "p" | "prev" | "back" => replay.ply = replay.ply.saturating_sub(1),
+            "f" | "first" => replay.ply = 0,
+            "l" | "last" => replay.ply = replay.last_ply(),
+            "next-game" if replay.index + 1 < games.len() => load(replay.index + 1, &mut replay),
+            "prev-game" if replay.index > 0 => load(replay.index - 1, &mut replay),
+            "next-game" | "prev-game" => {}
+            "g" | "game" => match words.next().and_then(|n| n.parse::<usize>().ok()) {
+                Some(number) if number >= 1 => load(number - 1, &mut replay),
+                _ => view.message = (format!("❌ Usage: g N (1-{})", games.len()), Some(Color::Red)),
+            },
+            "list" => {
+                view.showing_list = true;
+                let (_, rows) = view.layout.terminal;
+                let list = game_list(&games, replay.index, rows.saturating_sub(3) as usize);
+                tui::draw_overlay(&list.iter().map(String::as_str).collect::<Vec<_>>())?;
+                continue;
+            }
+            "flip" => ui.flip(),
+            "graph" if replay.evals.is_some() => view.show_graph = !view.show_graph,
+            "graph" => view.message = ("❌ No evaluations in this game; run `chess-cli annotate` first".to_string(), Some(Color::Red)),
+            "q" | "quit" | "exit" => break,
+            other => view.message = (format!("❌ Unknown command '{}'", other), Some(Color::Red)),
+        }
+        // A new game may bring or drop the graph, which changes the board size that fits.
+        view.fit(&mut ui, &replay)?;
+    }
+    Ok(())
+}
+
+const HINT: &str = "←/→ step, Home/End ends, PgUp/PgDn games; or type g N, list, graph, flip, q";
+
+/// The full-screen replay: board, game details and moves, with the evaluation graph below the board.
+struct ReplayView {
+    layout: Layout,
+    command: CommandLine,
+    message: Line,
+    show_graph: bool,
+    /// The game list covers the screen until the next key.
+    showing_list: bool,
+}
+
+impl ReplayView {
+    fn new(ui: &mut ChessUI, replay: &Replay) -> Self {
+        let mut view = ReplayView {
+            layout: Layout::for_terminal(ui.size()),
+            command: CommandLine::default(),
+            message: (String::new(), None),
+            show_graph: true,
+            showing_list: false,
+        };
+        view.layout = view.fit_layout(ui, replay);
+        view
+    }
+
+    /// Picks the board size and layout for the terminal and clears the screen for a full redraw.
+    fn fit(&mut self, ui: &mut ChessUI, replay: &Replay) -> Result<()> {
+        let layout = self.fit_layout(ui, replay);
+        if layout.board != self.layout.board || layout.terminal != self.layout.terminal {
+            execute!(io::stdout(), terminal::Clear(ClearType::All))?;
+        }
+        self.layout = layout;
+        Ok(())
+    }
+
+    fn fit_layout(&self, ui: &mut ChessUI, replay: &Replay) -> Layout {
+        let (columns, rows) = terminal::size().unwrap_or((80, 24));
+        // The pane below the board loses a row to the gap and two to the status and command lines.
+        let graph_rows = if self.show_graph && replay.evals.is_some() { eval_graph::HEIGHT + 3 } else { 0 };
+        ui.fit(columns, rows, (tui::SPARE.0, tui::SPARE.1.max(graph_rows)));
+        Layout::new(columns, rows, ui.size())
+    }
+
+    fn draw(&self, ui: &ChessUI, replay: &Replay, game_count: usize) -> Result<()> {
+        if self.showing_list {
+            return Ok(());
+        }
+        let layout = self.layout;
+        if !layout.fits() {
+            return tui::draw_too_small(&layout);
+        }
+        queue!(io::stdout(), cursor::Hide)?;
+        ui.draw_board(replay.board(), &BoardMarks::default(), layout.board.x, layout.board.y)?;
+
+        let game = &replay.game;
+        let tag = |name| game.tag(name).unwrap_or("?");
+        let title = format!("📜 Game {}/{}: {} - {} {}", replay.index + 1, game_count, tag("White"), tag("Black"), tag("Result"));
+        let details = format!("   {}, {}, {}", tag("Event"), tag("Site"), tag("Date"));
+        tui::draw_lines(layout.clocks, &[(title, None), (details, Some(Color::DarkGrey))])?;
+        tui::draw_lines(layout.info, &ply_lines(replay, layout.info.width as usize))?;
+
+        let moves = layout.moves;
+        let list = move_list(replay);
+        let current = replay.ply.checked_sub(1);
+        let list_rect = Rect::new(moves.x, moves.y + 1, moves.width, moves.height - 1);
+        list.draw(list_rect, current, list.scroll_showing(current, list_rect.height as usize))?;
+        tui::draw_title(moves, "Moves")?;
+
+        let graph = match replay.evals.as_ref().filter(|_| self.show_graph) {
+            Some(evals) => eval_graph::rows(game, evals, current, layout.engine.width as usize),
+            None => Vec::new(),
+        };
+        tui::draw_rows(layout.engine, &graph)?;
+
+        let status = if self.message.0.is_empty() { (HINT.to_string(), Some(Color::DarkGrey)) } else { self.message.clone() };
+        tui::draw_lines(layout.status, &[status])?;
+        self.command.draw(layout.command, "view> ")?;
+        io::stdout().flush()?;
+        Ok(())
+    }
+}
+
+/// The current ply with its evaluation, comment and variations.
+fn ply_lines(replay: &Replay, width: usize) -> Vec<Line> {
+    let game = &replay.game;
+    let mut lines = Vec::new();
+    let mut text = |text: String, color: Option<Color>| {
+        lines.extend(tui::wrap(&text, width).into_iter().map(|line| (line, color)));
+    };
+
+    if replay.ply == 0 {
+        text(format!("Start position ({} plies)", replay.last_ply()), None);
+        if let Some(comment) = game.moves.first().and_then(|m| m.starting_comment.as_ref()) {
+            text(format!("💬 {}", comment), None);
+        }
+    } else {
+        let before = &replay.boards[replay.ply - 1];
+        let played = &game.moves[replay.ply - 1];
+        text(format!("Ply {}/{}: {}", replay.ply, replay.last_ply(), move_label(before, played)), None);
+        match played.comment.as_deref().and_then(eval_graph::parse_eval) {
+            Some(eval) if eval.abs() >= 100.0 => {
+                text(format!("📈 {} is mating", if eval > 0.0 { "White" } else { "Black" }), None)
+            }
+            Some(eval) => text(format!("📈 {:+.2}", eval), None),
+            None => {}
+        }
+        let comment = played.comment.as_deref().map(pgn::strip_commands).unwrap_or_default();
+        if !comment.is_empty() {
+            text(format!("💬 {}", comment), None);
+        }
+        for variation in &played.variations {
+            let white_to_move = before.current_player() == Player::White;
+            text(format!("({})", pgn::movetext(variation, before.move_count(), white_to_move)), Some(Color::DarkGrey));
+        }
+    }
+    if replay.ply == replay.last_ply() {
+        if let Some(ref comment) = game.result_comment {
+            text(format!("💬 {}", comment), None);
+        }
+    }
+    lines
+}
+
+fn move_list(replay: &Replay) -> MoveList {
+    let start = &replay.boards[0];
+    MoveList {
+        entries: replay
+            .game
+            .moves
+            .iter()
+            .map(|played| MoveEntry { san: san_with_glyphs(played), time: None, eval: None })
+            .collect(),
+        first_number: start.move_count(),
+        black_first: start.current_player() == Player::Black,
+    }
+}
+
+fn move_label(before: &Board, played: &PgnMove) -> String {
+    let number = match before.current_player() {
+        Player::White => format!("{}.", before.move_count()),
+        Player::Black => format!("{}...", before.move_count()),
+    };
+    format!("{} {}", number, san_with_glyphs(played))
+}
+
+fn san_with_glyphs(played: &PgnMove) -> String {
+    let mut label = played.san.clone();
+    for glyph in played.nags.iter().filter_map(|&nag| pgn::nag_glyph(nag)) {
+        label.push_str(glyph);
+    }
+    label
+}
+
+/// Up to `rows` lines of the game list, around the `current` game.
+fn game_list(games: &[PgnGame], current: usize, rows: usize) -> Vec<String> {
+    let first = current.saturating_sub(rows / 2).min(games.len().saturating_sub(rows));
+    games
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(rows.max(1))
+        .map(|(index, game)| {
+            let tag = |name| game.tag(name).unwrap_or("?");
+            format!(
+                "{} {:>4}. {} - {} {} ({}, {})",
+                if index == current { "▶" } else { " " },
+                index + 1,
+                tag("White"),
+                tag("Black"),
+                tag("Result"),
+                tag("Event"),
+                tag("Date")
+            )
+        })
+        .collect()
+}
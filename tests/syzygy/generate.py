@@ -0,0 +1,526 @@
+#!/usr/bin/env python3
+"""Builds the KQvK, KRvK, KPvK, KBvK and KNvK Syzygy test tables in this directory.
+
+The positions are solved by retrograde analysis and written in the Syzygy
+file format: canonical Huffman codes over Re-Pair symbols, a sparse index and
+fixed-size blocks. The DTZ tables store White to move, mapped and in plies.
+KPvK is split by the leading pawn's file like every pawn table; KBvK and KNvK
+are drawn throughout and stored as single values.
+
+    python3 tests/syzygy/generate.py
+"""
+
+import heapq
+import os
+import struct
+from collections import Counter
+
+WDL_MAGIC = bytes([0x71, 0xE8, 0x23, 0x5D])
+DTZ_MAGIC = bytes([0xD7, 0x66, 0x0C, 0xA5])
+
+FLAG_MAPPED = 2
+FLAG_WIN_PLIES = 4
+FLAG_LOSS_PLIES = 8
+FLAG_SINGLE_VALUE = 128
+
+WHITE_KING, BLACK_KING = 6, 14
+QUEEN, ROOK, BISHOP, KNIGHT, PAWN = 5, 4, 3, 2, 1
+HEADER_UNSYMMETRIC, HEADER_PAWNS = 1, 2
+
+BLOCK_SIZE_LOG = 8
+SPAN_LOG = 7
+
+KING_STEPS = [(df, dr) for df in (-1, 0, 1) for dr in (-1, 0, 1) if df or dr]
+ROOK_DIRECTIONS = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+QUEEN_DIRECTIONS = ROOK_DIRECTIONS + [(1, 1), (1, -1), (-1, 1), (-1, -1)]
+
+
+def step(square, df, dr):
+    file, rank = square % 8 + df, square // 8 + dr
+    return rank * 8 + file if 0 <= file < 8 and 0 <= rank < 8 else None
+
+
+def adjacent(a, b):
+    return max(abs(a % 8 - b % 8), abs(a // 8 - b // 8)) <= 1
+
+
+def ray_squares(square, directions, blockers):
+    for df, dr in directions:
+        target = step(square, df, dr)
+        while target is not None:
+            yield target
+            if target in blockers:
+                break
+            target = step(target, df, dr)
+
+
+def solve(directions):
+    """Plies to mate for White to move (wins) and Black to move (losses), keyed by (wk, piece, bk)."""
+    def attacked(piece, target, blockers):
+        return target in ray_squares(piece, directions, blockers)
+
+    def legal(wk, piece, bk):
+        return len({wk, piece, bk}) == 3 and not adjacent(wk, bk)
+
+    squares = range(64)
+    white_moves, black_moves, black_draws, mated = {}, {}, set(), set()
+    for wk in squares:
+        for piece in squares:
+            for bk in squares:
+                if not legal(wk, piece, bk):
+                    continue
+                in_check = attacked(piece, bk, {wk})
+                if not in_check:
+                    children = []
+                    for df, dr in KING_STEPS:
+                        target = step(wk, df, dr)
+                        if target is not None and target != piece and legal(target, piece, bk):
+                            children.append((target, piece, bk))
+                    for target in ray_squares(piece, directions, {wk, bk}):
+                        if target not in (wk, bk):
+                            children.append((wk, target, bk))
+                    white_moves[(wk, piece, bk)] = children
+
+                children, can_capture = [], False
+                for df, dr in KING_STEPS:
+                    target = step(bk, df, dr)
+                    if target is None or target == wk or adjacent(target, wk):
+                        continue
+                    if target == piece:
+                        can_capture = True
+                    elif not attacked(piece, target, {wk}):
+                        children.append((wk, piece, target))
+                if can_capture or (not children and not in_check):
+                    black_draws.add((wk, piece, bk))
+                elif not children:
+                    mated.add((wk, piece, bk))
+                black_moves[(wk, piece, bk)] = children
+
+    wins, losses = {}, {position: 0 for position in mated}
+    plies = 0
+    while True:
+        plies += 1
+        found = {}
+        if plies % 2 == 1:
+            for position, children in white_moves.items():
+                if position not in wins and any(losses.get(child) == plies - 1 for child in children):
+                    found[position] = plies
+            wins.update(found)
+        else:
+            for position, children in black_moves.items():
+                if position in losses or position in black_draws:
+                    continue
+                if all(child in wins for child in children):
+                    found[position] = plies
+            losses.update(found)
+        if not found and plies > 2 and plies % 2 == 0:
+            break
+    return white_moves, black_moves, wins, losses
+
+
+def off_diagonal(square):
+    return square // 8 - square % 8
+
+
+def build_maps():
+    b1h1h7 = {}
+    for square in range(64):
+        if off_diagonal(square) < 0:
+            b1h1h7[square] = len(b1h1h7)
+    a1d1d4 = {}
+    for square in range(28):
+        if off_diagonal(square) < 0 and square % 8 <= 3:
+            a1d1d4[square] = len(a1d1d4)
+    for square in (0, 9, 18, 27):
+        a1d1d4[square] = len(a1d1d4)
+    return b1h1h7, a1d1d4
+
+
+MAP_B1H1H7, MAP_A1D1D4 = build_maps()
+TABLE_SIZE = 31332
+PAWN_TABLE_SIZE = 6 * 63 * 62
+
+
+def table_index(squares):
+    """Index of three unique pieces, the first one mapped into the a1-d1-d4 triangle."""
+    squares = list(squares)
+    if squares[0] % 8 > 3:
+        squares = [s ^ 7 for s in squares]
+    if squares[0] // 8 > 3:
+        squares = [s ^ 56 for s in squares]
+    for i in range(3):
+        if off_diagonal(squares[i]) == 0:
+            continue
+        if off_diagonal(squares[i]) > 0:
+            squares[i:] = [((s >> 3) | (s << 3)) & 63 for s in squares[i:]]
+        break
+
+    s0, s1, s2 = squares
+    adjust1 = int(s1 > s0)
+    adjust2 = int(s2 > s0) + int(s2 > s1)
+    if off_diagonal(s0):
+        return (MAP_A1D1D4[s0] * 63 + (s1 - adjust1)) * 62 + s2 - adjust2
+    if off_diagonal(s1):
+        return (6 * 63 + (s0 // 8) * 28 + MAP_B1H1H7[s1]) * 62 + s2 - adjust2
+    if off_diagonal(s2):
+        return 6 * 63 * 62 + 4 * 28 * 62 + (s0 // 8) * 7 * 28 + (s1 // 8 - adjust1) * 28 + MAP_B1H1H7[s2]
+    return 6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + (s0 // 8) * 7 * 6 + (s1 // 8 - adjust1) * 6 + s2 // 8 - adjust2
+
+
+def pawn_table_index(squares):
+    """File and index of a pawn, then two kings; the pawn is mirrored onto files a-d."""
+    squares = list(squares)
+    if squares[0] % 8 > 3:
+        squares = [s ^ 7 for s in squares]
+    pawn, s1, s2 = squares
+    # One leading pawn is numbered by its rank; each king skips the squares before it.
+    lead = pawn // 8 - 1
+    n1 = s1 - int(s1 > pawn)
+    n2 = s2 - int(s2 > pawn) - int(s2 > s1)
+    return pawn % 8, lead + 6 * n1 + 6 * 63 * n2
+
+
+def fill_table(values_by_position, order, size=TABLE_SIZE, index_of=table_index):
+    """Lays the values out by index; unused indices repeat their neighbour so they compress."""
+    table = [None] * size
+    for (wk, piece, bk), value in values_by_position.items():
+        by_code = {WHITE_KING: wk, QUEEN: piece, ROOK: piece, BLACK_KING: bk}
+        index = index_of([by_code[code] for code in order])
+        assert table[index] in (None, value), "symmetric positions disagree"
+        table[index] = value
+    previous = next(value for value in table if value is not None)
+    for i, value in enumerate(table):
+        if value is None:
+            table[i] = previous
+        previous = table[i]
+    return table
+
+
+def re_pair(sequence, symbols, rounds=24, max_values=32):
+    """Replaces the most frequent adjacent symbol pairs with new symbols."""
+    lengths = {symbol: 1 for symbol in set(sequence)}
+    for _ in range(rounds):
+        pairs = Counter(zip(sequence, sequence[1:]))
+        candidates = [(count, pair) for pair, count in pairs.items()
+                      if lengths[pair[0]] + lengths[pair[1]] <= max_values]
+        if not candidates:
+            break
+        count, (left, right) = max(candidates)
+        if count < 4:
+            break
+        new = len(symbols)
+        symbols.append((left, right))
+        lengths[new] = lengths[left] + lengths[right]
+        replaced, i = [], 0
+        while i < len(sequence):
+            if i + 1 < len(sequence) and sequence[i] == left and sequence[i + 1] == right:
+                replaced.append(new)
+                i += 2
+            else:
+                replaced.append(sequence[i])
+                i += 1
+        sequence = replaced
+    return sequence, lengths
+
+
+def huffman_lengths(frequencies):
+    if len(frequencies) == 1:
+        return {symbol: 1 for symbol in frequencies}
+    heap = [(count, i, [symbol]) for i, (symbol, count) in enumerate(frequencies.items())]
+    heapq.heapify(heap)
+    lengths = {symbol: 0 for symbol in frequencies}
+    tiebreak = len(heap)
+    while len(heap) > 1:
+        count_a, _, group_a = heapq.heappop(heap)
+        count_b, _, group_b = heapq.heappop(heap)
+        for symbol in group_a + group_b:
+            lengths[symbol] += 1
+        heapq.heappush(heap, (count_a + count_b, tiebreak, group_a + group_b))
+        tiebreak += 1
+    return lengths
+
+
+def encode(values, flags):
+    """The pairs header, sparse index, block lengths and data of one compressed table."""
+    distinct = sorted(set(values))
+    if len(distinct) == 1:
+        return bytes([flags | FLAG_SINGLE_VALUE, distinct[0]]), b"", b"", []
+
+    symbols = [(value, 0xFFF) for value in distinct]
+    leaf = {value: i for i, value in enumerate(distinct)}
+    sequence, value_counts = re_pair([leaf[v] for v in values], symbols)
+    code_lengths = huffman_lengths(Counter(sequence))
+
+    # Symbols are numbered from the longest codes up; uncoded ones go last.
+    max_len, min_len = max(code_lengths.values()), min(code_lengths.values())
+    coded = sorted(code_lengths, key=lambda s: (-code_lengths[s], s))
+    numbering = coded + [s for s in range(len(symbols)) if s not in code_lengths]
+    number = {symbol: i for i, symbol in enumerate(numbering)}
+
+    levels = max_len - min_len + 1
+    counts = [0] * levels
+    for symbol in coded:
+        counts[code_lengths[symbol] - min_len] += 1
+    lowest = [0] * levels
+    for i in range(levels - 2, -1, -1):
+        lowest[i] = lowest[i + 1] + counts[i + 1]
+    base = [0] * levels
+    for i in range(levels - 2, -1, -1):
+        assert (base[i + 1] + counts[i + 1]) % 2 == 0, "Huffman code is not complete"
+        base[i] = (base[i + 1] + counts[i + 1]) // 2
+
+    def code(symbol):
+        level = code_lengths[symbol] - min_len
+        return base[level] + number[symbol] - lowest[level], code_lengths[symbol]
+
+    block_size = 1 << BLOCK_SIZE_LOG
+    blocks, bits, block_values, block_counts = [], [], 0, []
+    for symbol in sequence:
+        value, length = code(symbol)
+        if len(bits) + length > block_size * 8:
+            blocks.append(bits)
+            block_counts.append(block_values)
+            bits, block_values = [], 0
+        bits.extend((value >> (length - 1 - k)) & 1 for k in range(length))
+        block_values += value_counts[symbol]
+    blocks.append(bits)
+    block_counts.append(block_values)
+    assert sum(block_counts) == len(values) and max(block_counts) <= 65536
+
+    data = []
+    for bits in blocks:
+        bits = bits + [0] * (block_size * 8 - len(bits))
+        data.append(bytes(int("".join(map(str, bits[i:i + 8])), 2) for i in range(0, len(bits), 8)))
+
+    starts = [sum(block_counts[:b]) for b in range(len(blocks))]
+    span = 1 << SPAN_LOG
+    sparse = b""
+    for k in range((len(values) + span - 1) // span):
+        middle = k * span + span // 2
+        block = max(b for b in range(len(blocks)) if starts[b] <= middle)
+        sparse += struct.pack("<IH", block, middle - starts[block])
+    block_lengths = b"".join(struct.pack("<H", count - 1) for count in block_counts)
+
+    header = bytes([flags, BLOCK_SIZE_LOG, SPAN_LOG, 0]) + struct.pack("<I", len(blocks))
+    header += bytes([max_len, min_len]) + b"".join(struct.pack("<H", l) for l in lowest)
+    header += struct.pack("<H", len(symbols))
+    for symbol in numbering:
+        left, right = symbols[symbol]
+        if right != 0xFFF:
+            left, right = number[left], number[right]
+        header += bytes([left & 0xFF, (left >> 8) | ((right & 0xF) << 4), right >> 4])
+    if len(symbols) % 2:
+        header += b"\0"
+    return header, sparse, block_lengths, data
+
+
+def pad(buffer, alignment):
+    return buffer + b"\0" * (-len(buffer) % alignment)
+
+
+def write_table(path, magic, header, files):
+    """`files` holds one (orders, sides, dtz_map) per leading-pawn file, or one for a pawnless
+    table: `orders` gives each side's piece order and `sides` its (values, flags)."""
+    out = magic + bytes([header])
+    for orders, _, _ in files:
+        out += bytes([0])
+        for i in range(3):
+            low = orders[0][i]
+            high = orders[1][i] if len(orders) > 1 else 0
+            out += bytes([low | (high << 4)])
+    out = pad(out, 2)
+
+    encoded = [[encode(values, flags) for values, flags in sides] for _, sides, _ in files]
+    for sides in encoded:
+        for header_bytes, _, _, _ in sides:
+            out += header_bytes
+    if magic == DTZ_MAGIC:
+        for _, _, dtz_map in files:
+            for entries in dtz_map or []:
+                out += bytes([len(entries)]) + bytes(entries)
+        out = pad(out, 2)
+    for sides in encoded:
+        for _, sparse, _, _ in sides:
+            out += sparse
+    for sides in encoded:
+        for _, _, block_lengths, _ in sides:
+            out += block_lengths
+    for sides in encoded:
+        for _, _, _, data in sides:
+            out = pad(out, 64)
+            out += b"".join(data)
+
+    with open(path, "wb") as file:
+        file.write(out)
+
+
+def dtz_side(distances_by_position, order, size=TABLE_SIZE, index_of=table_index):
+    """Only wins are stored; the map turns symbols into plies minus one."""
+    distances = sorted({plies - 1 for plies in distances_by_position.values()})
+    symbol = {distance: i for i, distance in enumerate(distances)}
+    stored = {p: symbol[plies - 1] for p, plies in distances_by_position.items()}
+    flags = FLAG_MAPPED | FLAG_WIN_PLIES | FLAG_LOSS_PLIES
+    return (fill_table(stored, order, size, index_of), flags), [distances, [], [], []]
+
+
+def generate(name, piece, solution, directory):
+    white_moves, black_moves, wins, losses = solution
+    # Different orders per side exercise the prober's piece reordering.
+    white_order = [WHITE_KING, piece, BLACK_KING]
+    black_order = [BLACK_KING, WHITE_KING, piece]
+
+    white_wdl = {p: 4 if p in wins else 2 for p in white_moves}
+    black_wdl = {p: 0 if p in losses else 2 for p in black_moves}
+    sides = [(fill_table(white_wdl, white_order), 0), (fill_table(black_wdl, black_order), 0)]
+    write_table(os.path.join(directory, name + ".rtbw"), WDL_MAGIC, HEADER_UNSYMMETRIC,
+                [([white_order, black_order], sides, None)])
+
+    dtz, dtz_map = dtz_side({p: wins[p] for p in white_moves if p in wins}, white_order)
+    write_table(os.path.join(directory, name + ".rtbz"), DTZ_MAGIC, HEADER_UNSYMMETRIC,
+                [([white_order], [dtz], dtz_map)])
+    print("{}: longest win {} plies, {} drawn White-to-move positions".format(
+        name, max(wins.values()), len(white_moves) - len(wins)))
+
+
+def generate_drawn(name, piece, directory):
+    order = [WHITE_KING, piece, BLACK_KING]
+    write_table(os.path.join(directory, name + ".rtbw"), WDL_MAGIC, HEADER_UNSYMMETRIC,
+                [([order, order], [([2], 0), ([2], 0)], None)])
+    write_table(os.path.join(directory, name + ".rtbz"), DTZ_MAGIC, HEADER_UNSYMMETRIC,
+                [([order], [([0], 0)], None)])
+
+
+def solve_pawn(queen_losses, rook_losses):
+    """White to move wins with their DTZ in plies, and Black to move losses, keyed by (wk, pawn, bk)."""
+    def pawn_attacks(pawn):
+        return {target for target in (step(pawn, -1, 1), step(pawn, 1, 1)) if target is not None}
+
+    white_moves, black_moves = {}, {}
+    for wk in range(64):
+        for pawn in range(8, 56):
+            for bk in range(64):
+                if len({wk, pawn, bk}) < 3 or adjacent(wk, bk):
+                    continue
+                position = (wk, pawn, bk)
+                # Children are ("zeroing", won) for pawn moves, or ("king", position) for king moves.
+                if bk not in pawn_attacks(pawn):
+                    children = []
+                    for df, dr in KING_STEPS:
+                        target = step(wk, df, dr)
+                        if target is not None and target != pawn and not adjacent(target, bk):
+                            children.append(("king", (target, pawn, bk)))
+                    pushes = [pawn + 8] if pawn + 8 not in (wk, bk) else []
+                    if pawn < 16 and pushes and pawn + 16 not in (wk, bk):
+                        pushes.append(pawn + 16)
+                    for target in pushes:
+                        if target >= 56:
+                            won = (wk, target, bk) in queen_losses or (wk, target, bk) in rook_losses
+                            children.append(("zeroing", won))
+                        else:
+                            children.append(("zeroing", (wk, target, bk)))
+                    white_moves[position] = children
+
+                children, escapes = [], False
+                for df, dr in KING_STEPS:
+                    target = step(bk, df, dr)
+                    if target is None or target == wk or adjacent(target, wk) or target in pawn_attacks(pawn):
+                        continue
+                    if target == pawn:
+                        escapes = True
+                    else:
+                        children.append((wk, pawn, target))
+                black_moves[position] = (children, escapes or (not children and bk not in pawn_attacks(pawn)))
+
+    # Win/loss by fixed point: pawn pushes lead to Black-to-move positions, promotions are decided.
+    wins, losses = set(), set()
+    changed = True
+    while changed:
+        changed = False
+        for position, children in white_moves.items():
+            if position in wins:
+                continue
+            if any(child is True or (kind in ("king", "zeroing") and child in losses) for kind, child in children):
+                wins.add(position)
+                changed = True
+        for position, (children, drawn) in black_moves.items():
+            if position in losses or drawn:
+                continue
+            if all(child in wins for child in children):
+                losses.add(position)
+                changed = True
+
+    # DTZ by increasing distance: a winning pawn move is 1 ply, king moves add the reply.
+    white_dtz, black_dtz = {}, {}
+    for position, children in white_moves.items():
+        if any(kind == "zeroing" and (child is True or child in losses) for kind, child in children):
+            white_dtz[position] = 1
+    plies = 1
+    while True:
+        plies += 1
+        found = 0
+        if plies % 2 == 0:
+            for position in losses:
+                children, _ = black_moves[position]
+                if position not in black_dtz and all(child in white_dtz for child in children):
+                    black_dtz[position] = 1 + max((white_dtz[child] for child in children), default=-1)
+                    found += 1
+        else:
+            for position in wins:
+                if position in white_dtz:
+                    continue
+                distances = [black_dtz[child] for kind, child in white_moves[position]
+                             if kind == "king" and child in black_dtz]
+                if distances and 1 + min(distances) == plies:
+                    white_dtz[position] = plies
+                    found += 1
+        # No new wins at one length means none are longer.
+        if not found and plies % 2 == 1:
+            assert len(white_dtz) == len(wins), "wins without a distance"
+            break
+    return white_moves, black_moves, white_dtz, losses
+
+
+def generate_pawn(queen, rook, directory):
+    queen_losses, rook_losses = queen[3], rook[3]
+    white_moves, black_moves, white_dtz, losses = solve_pawn(queen_losses, rook_losses)
+    white_order = [PAWN, WHITE_KING, BLACK_KING]
+    black_order = [PAWN, BLACK_KING, WHITE_KING]
+    size = PAWN_TABLE_SIZE
+
+    def on_file(values, file):
+        return {(wk, pawn, bk): value for (wk, pawn, bk), value in values.items()
+                if pawn_table_index([pawn, wk, bk])[0] == file}
+
+    def index_of(squares):
+        return pawn_table_index(squares)[1]
+
+    def order_for(order):
+        # fill_table looks pieces up by code; the pawn takes the piece slot.
+        return [QUEEN if code == PAWN else code for code in order]
+
+    wdl_files, dtz_files = [], []
+    for file in range(4):
+        white_wdl = on_file({p: 4 if p in white_dtz else 2 for p in white_moves}, file)
+        black_wdl = on_file({p: 0 if p in losses else 2 for p in black_moves}, file)
+        sides = [(fill_table(white_wdl, order_for(white_order), size, index_of), 0),
+                 (fill_table(black_wdl, order_for(black_order), size, index_of), 0)]
+        wdl_files.append(([white_order, black_order], sides, None))
+        dtz, dtz_map = dtz_side(on_file(white_dtz, file), order_for(white_order), size, index_of)
+        dtz_files.append(([white_order], [dtz], dtz_map))
+
+    header = HEADER_UNSYMMETRIC | HEADER_PAWNS
+    write_table(os.path.join(directory, "KPvK.rtbw"), WDL_MAGIC, header, wdl_files)
+    write_table(os.path.join(directory, "KPvK.rtbz"), DTZ_MAGIC, header, dtz_files)
+    print("KPvK: longest DTZ {} plies, {} drawn White-to-move positions".format(
+        max(white_dtz.values()), len(white_moves) - len(white_dtz)))
+
+
+if __name__ == "__main__":
+    here = os.path.dirname(os.path.abspath(__file__))
+    queen = solve(QUEEN_DIRECTIONS)
+    rook = solve(ROOK_DIRECTIONS)
+    generate("KQvK", QUEEN, queen, here)
+    generate("KRvK", ROOK, rook, here)
+    generate_pawn(queen, rook, here)
+    generate_drawn("KBvK", BISHOP, here)
+    generate_drawn("KNvK", KNIGHT, here)